@@ -0,0 +1,126 @@
+use chess::*;
+
+use crate::evaluator::*;
+use crate::search::*;
+
+struct FixedDepthHandler {
+    depth: u8,
+    result: Option<SearchResult>
+}
+
+impl FixedDepthHandler {
+    fn done(&self) -> bool {
+        self.result.as_ref().map(|r| r.depth >= self.depth).unwrap_or_default()
+    }
+}
+
+impl LunaticHandler for FixedDepthHandler {
+    fn time_up(&mut self) -> bool {
+        self.done()
+    }
+
+    fn search_result(&mut self, search_result: SearchResult) {
+        //Keep the first result at the requested depth
+        if !self.done() {
+            self.result = Some(search_result);
+        }
+    }
+}
+
+///Searches the position after `moves` to a fixed depth.
+///Returns `None` if the game is over.
+pub fn analyze_position(
+    init_pos: &Board,
+    moves: &[ChessMove],
+    options: &SearchOptions,
+    depth: u8
+) -> Option<SearchResult> {
+    let mut handler = FixedDepthHandler {
+        depth,
+        result: None
+    };
+    let mut state = LunaticSearchState::new(
+        &mut handler,
+        init_pos,
+        moves.iter().copied(),
+        options.clone()
+    );
+    state.search();
+    handler.result
+}
+
+///Walks a line of moves, searching the position after each move to a fixed depth.
+///`moves` are the moves leading from `init_pos` to the start of the line.
+///Each result is from the perspective of the side to move after that move,
+///and is `None` if the game is over at that point.
+pub fn analyze_line(
+    init_pos: &Board,
+    moves: impl IntoIterator<Item=ChessMove>,
+    line: &[ChessMove],
+    options: &SearchOptions,
+    depth: u8
+) -> Vec<Option<SearchResult>> {
+    let mut moves: Vec<_> = moves.into_iter().collect();
+    let mut results = Vec::with_capacity(line.len());
+    for &mv in line {
+        moves.push(mv);
+        results.push(analyze_position(init_pos, &moves, options, depth));
+    }
+    results
+}
+
+#[derive(Debug, Clone)]
+pub struct MoveCheck {
+    ///The engine's choice in the position before the move
+    pub best_move: ChessMove,
+    pub best_value: Eval,
+    ///The value of the played move for the side that played it
+    pub played_value: Eval
+}
+
+impl MoveCheck {
+    ///How much worse the played move is than the best move in centipawns.
+    ///Missing or walking into a mate counts as a far larger loss than any material.
+    pub fn loss(&self) -> i32 {
+        (self.best_value.to_bits() as i32 - self.played_value.to_bits() as i32).max(0)
+    }
+
+    pub fn is_blunder(&self, threshold: i32) -> bool {
+        self.loss() >= threshold
+    }
+}
+
+///Compares a move against the engine's best move, for quick feedback on a human move.
+///`mv` must be legal in the position after `moves`.
+///Returns `None` if the game was already over.
+pub fn check_move(
+    init_pos: &Board,
+    moves: &[ChessMove],
+    mv: ChessMove,
+    options: &SearchOptions,
+    depth: u8
+) -> Option<MoveCheck> {
+    let best = analyze_position(init_pos, moves, options, depth)?;
+    let played_value = if mv == best.mv {
+        best.value
+    } else {
+        let mut moves = moves.to_vec();
+        moves.push(mv);
+        //The reply is searched one ply shallower so both values see equally far
+        match analyze_position(init_pos, &moves, options, depth.saturating_sub(1)) {
+            Some(reply) => -reply.value,
+            None => {
+                let board = moves.iter().fold(*init_pos, |board, &mv| board.make_move_new(mv));
+                match board.status() {
+                    BoardStatus::Checkmate => Eval::mate_in(1),
+                    _ => Eval::DRAW
+                }
+            }
+        }
+    };
+    Some(MoveCheck {
+        best_move: best.mv,
+        best_value: best.value,
+        played_value
+    })
+}
@@ -0,0 +1,88 @@
+use std::fmt::Write;
+
+use crate::evaluator::SearchEvaluator;
+use crate::moves::QuiescenceChecks;
+use crate::search::SearchOptions;
+use crate::table::TranspositionTable;
+
+///Dumps the effective configuration of a search as YAML,
+///so bug reports and tuning runs can record the exact setup.
+pub fn dump_config(options: &SearchOptions) -> String {
+    let mut out = String::new();
+    macro_rules! fields {
+        ($($field:ident)*) => {
+            $(writeln!(out, "  {}: {}", stringify!($field), options.$field).unwrap();)*
+        }
+    }
+    out.push_str("search:\n");
+    fields! {
+        late_move_reduction
+        late_move_leeway
+        null_move_pruning
+        null_move_reduction
+        null_move_depth_divisor
+        null_move_eval_margin
+        null_move_verification_depth
+        internal_iterative_deepening
+        iid_min_depth
+        iid_reduction
+        internal_iterative_reduction
+        passed_pawn_extension
+        see_pruning
+        see_pruning_max_depth
+        see_pruning_quiet_margin
+        see_pruning_capture_margin
+        easy_move_depth
+        easy_move_margin
+        quiescence_see_pruning
+    }
+    let quiescence_checks = match options.quiescence_checks {
+        QuiescenceChecks::None => "none",
+        QuiescenceChecks::All => "all",
+        QuiescenceChecks::NonLosing => "non_losing"
+    };
+    writeln!(out, "  quiescence_checks: {}", quiescence_checks).unwrap();
+    fields! {
+        quiescence_table_reads
+        quiescence_table_writes
+        max_depth
+        endgame_material
+        endgame_max_depth
+        endgame_light_eval
+        handicap_margin
+        handicap_seed
+        random_opening_plies
+        random_opening_margin
+    }
+    match &options.root_moves {
+        Some(moves) => {
+            let moves = moves.iter().map(|mv| mv.to_string()).collect::<Vec<_>>();
+            writeln!(out, "  root_moves: [{}]", moves.join(", ")).unwrap();
+        }
+        None => out.push_str("  root_moves: null\n")
+    }
+    fields! {
+        max_principal_variation_length
+        max_nodes
+    }
+
+    out.push_str("evaluator:\n");
+    writeln!(out, "  checksum: 0x{:08x}", options.evaluator.standard().checksum()).unwrap();
+    if let SearchEvaluator::Blended(evaluator) = &options.evaluator {
+        writeln!(out, "  secondary_checksum: 0x{:08x}", evaluator.secondary.checksum()).unwrap();
+        writeln!(out, "  blend_mode: {:?}", evaluator.mode).unwrap();
+    }
+
+    let size = options.transposition_table_size;
+    let entries = TranspositionTable::rounded_entries(size);
+    out.push_str("transposition_table:\n");
+    writeln!(out, "  requested_bytes: {}", size).unwrap();
+    writeln!(out, "  entries: {}", entries).unwrap();
+    writeln!(out, "  bytes: {}", entries * TranspositionTable::ENTRY_SIZE).unwrap();
+
+    //The search is single threaded and has no opening book or tablebases.
+    out.push_str("threads: 1\n");
+    out.push_str("book: null\n");
+    out.push_str("tablebases: null\n");
+    out
+}
@@ -0,0 +1,185 @@
+use std::sync::{Arc, Mutex};
+
+use chess::*;
+
+use serde::{Serialize, Deserialize};
+
+use super::*;
+
+///How a `BlendedEvaluator` combines its evaluators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    ///A weighted average, with the secondary evaluator's weight in `BlendedEvaluator::FULL_WEIGHT`ths
+    Blend(i16),
+    ///Only the primary evaluator's value is used, but positions where the secondary evaluator
+    ///disagrees by more than this many centipawns are recorded as divergences.
+    Compare(i16)
+}
+
+///A position where the evaluators of a `BlendedEvaluator` in compare mode disagreed
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub board: Board,
+    pub primary: Eval,
+    pub secondary: Eval
+}
+
+impl Divergence {
+    ///How much higher the secondary evaluation is, in centipawns.
+    pub fn difference(&self) -> i32 {
+        self.secondary.to_bits() as i32 - self.primary.to_bits() as i32
+    }
+}
+
+///Combines two evaluators, to phase in a new evaluator or tuned parameters
+///while measuring where they disagree with the current one.
+///Clones share the collected totals.
+#[derive(Debug, Clone)]
+pub struct BlendedEvaluator<A, B> {
+    pub primary: A,
+    pub secondary: B,
+    pub mode: BlendMode,
+    ///The most divergences kept. Later ones are counted but dropped.
+    pub max_divergences: usize,
+    stats: Arc<Mutex<CompareStats>>
+}
+
+///Totals collected by a `BlendedEvaluator` in compare mode
+#[derive(Debug, Clone, Default)]
+pub struct CompareStats {
+    ///Every position evaluated
+    pub positions: u64,
+    ///Positions where the evaluators disagreed by more than the threshold
+    pub divergent_positions: u64,
+    ///The sum of absolute differences over every position, in centipawns
+    pub total_difference: u64,
+    pub divergences: Vec<Divergence>
+}
+
+impl<A: Evaluator, B: Evaluator> BlendedEvaluator<A, B> {
+    pub const FULL_WEIGHT: i16 = 64;
+
+    pub fn new(primary: A, secondary: B, mode: BlendMode) -> Self {
+        Self {
+            primary,
+            secondary,
+            mode,
+            max_divergences: 1000,
+            stats: Arc::new(Mutex::new(CompareStats::default()))
+        }
+    }
+
+    ///The totals collected so far in compare mode.
+    pub fn stats(&self) -> CompareStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    ///Takes the totals collected so far, resetting them.
+    pub fn take_stats(&self) -> CompareStats {
+        std::mem::take(&mut *self.stats.lock().unwrap())
+    }
+}
+
+impl<A: Evaluator, B: Evaluator> Evaluator for BlendedEvaluator<A, B> {
+    fn evaluate(&self, board: &Board) -> Eval {
+        let primary = self.primary.evaluate(board);
+        let secondary = self.secondary.evaluate(board);
+        match self.mode {
+            BlendMode::Blend(weight) => {
+                let full = Self::FULL_WEIGHT as i32;
+                let weight = weight.clamp(0, Self::FULL_WEIGHT) as i32;
+                let blended = primary.to_bits() as i32 * (full - weight) + secondary.to_bits() as i32 * weight;
+                Eval::cp((blended / full) as i16)
+            }
+            BlendMode::Compare(threshold) => {
+                let difference = (secondary.to_bits() as i32 - primary.to_bits() as i32).abs();
+                let mut stats = self.stats.lock().unwrap();
+                stats.positions += 1;
+                stats.total_difference += difference as u64;
+                if difference > threshold as i32 {
+                    stats.divergent_positions += 1;
+                    if stats.divergences.len() < self.max_divergences {
+                        stats.divergences.push(Divergence {
+                            board: *board,
+                            primary,
+                            secondary
+                        });
+                    }
+                }
+                primary
+            }
+        }
+    }
+}
+
+///The evaluator used by a search.
+//The standard evaluator is kept inline, as it's the one almost every search uses.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SearchEvaluator {
+    Standard(StandardEvaluator),
+    ///Evaluated from scratch at every node, as only the primary evaluator's state is tracked.
+    #[serde(skip)]
+    Blended(Box<BlendedEvaluator<StandardEvaluator, StandardEvaluator>>)
+}
+
+impl Default for SearchEvaluator {
+    fn default() -> Self {
+        Self::Standard(StandardEvaluator::default())
+    }
+}
+
+impl From<StandardEvaluator> for SearchEvaluator {
+    fn from(evaluator: StandardEvaluator) -> Self {
+        Self::Standard(evaluator)
+    }
+}
+
+impl SearchEvaluator {
+    ///The standard evaluator, or the primary one if blended.
+    ///Its piece values and halfmove scaling are used for both.
+    pub fn standard(&self) -> &StandardEvaluator {
+        match self {
+            Self::Standard(evaluator) => evaluator,
+            Self::Blended(evaluator) => &evaluator.primary
+        }
+    }
+
+    pub fn set_light(&mut self, light: bool) {
+        match self {
+            Self::Standard(evaluator) => evaluator.light = light,
+            Self::Blended(evaluator) => {
+                evaluator.primary.light = light;
+                evaluator.secondary.light = light;
+            }
+        }
+    }
+
+    pub fn eval_state(&self, board: &Board) -> EvalState {
+        self.standard().eval_state(board)
+    }
+
+    pub fn update_eval_state(&self, state: &EvalState, board: &Board, mv: ChessMove) -> EvalState {
+        self.standard().update_eval_state(state, board, mv)
+    }
+
+    pub fn evaluate_with_state(&self, board: &Board, state: &EvalState) -> Eval {
+        match self {
+            Self::Standard(evaluator) => evaluator.evaluate_with_state(board, state),
+            Self::Blended(evaluator) => evaluator.evaluate(board)
+        }
+    }
+
+    pub fn scale_for_halfmove_clock(&self, value: Eval, halfmove_clock: u8) -> Eval {
+        self.standard().scale_for_halfmove_clock(value, halfmove_clock)
+    }
+}
+
+impl Evaluator for SearchEvaluator {
+    fn evaluate(&self, board: &Board) -> Eval {
+        match self {
+            Self::Standard(evaluator) => evaluator.evaluate(board),
+            Self::Blended(evaluator) => evaluator.evaluate(board)
+        }
+    }
+}
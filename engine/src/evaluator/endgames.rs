@@ -0,0 +1,145 @@
+use chess::*;
+
+use super::*;
+
+///How a specialized endgame adjusts the generic evaluation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndgameEval {
+    ///Scales the evaluation towards zero, in `DrawishScaling::FULL`ths
+    Scale(i16),
+    ///Replaces the evaluation. From the strong side's perspective when returned by an endgame.
+    Exact(Eval)
+}
+
+///Piece counts for one side excluding the king, in `ALL_PIECES` order.
+type Material = [u8; NUM_PIECES - 1];
+
+///Parses a material signature like "KRPvKR" into the strong and weak side's material.
+const fn parse_material(name: &str) -> (Material, Material) {
+    let bytes = name.as_bytes();
+    let mut material = [[0; NUM_PIECES - 1]; 2];
+    let mut side = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'P' => material[side][0] += 1,
+            b'N' => material[side][1] += 1,
+            b'B' => material[side][2] += 1,
+            b'R' => material[side][3] += 1,
+            b'Q' => material[side][4] += 1,
+            b'K' => {}
+            b'v' => side = 1,
+            _ => panic!("Invalid endgame material")
+        }
+        i += 1;
+    }
+    (material[0], material[1])
+}
+
+///An endgame with its own evaluation, recognized by its exact material.
+pub struct Endgame {
+    ///The material, stronger side first, like "KRPvKR"
+    pub name: &'static str,
+    strong: Material,
+    weak: Material,
+    eval: fn(&StandardEvaluator, &Board, Color) -> EndgameEval
+}
+
+macro_rules! endgames {
+    ($($name:literal => $eval:expr,)*) => {
+        ///Every specialized endgame.
+        ///Adding one only requires an entry here and its evaluation function.
+        pub const ENDGAMES: &[Endgame] = &[$({
+            let (strong, weak) = parse_material($name);
+            Endgame {
+                name: $name,
+                strong,
+                weak,
+                eval: $eval
+            }
+        }),*];
+    }
+}
+
+endgames! {
+    "KRPvKR" => rook_and_pawn_vs_rook,
+    "KQvKP" => queen_vs_pawn,
+}
+
+///The most pieces, kings included, of any specialized endgame.
+const MAX_ENDGAME_PIECES: u32 = {
+    let mut max = 0;
+    let mut i = 0;
+    while i < ENDGAMES.len() {
+        let mut pieces = 2;
+        let mut piece = 0;
+        while piece < NUM_PIECES - 1 {
+            pieces += ENDGAMES[i].strong[piece] as u32 + ENDGAMES[i].weak[piece] as u32;
+            piece += 1;
+        }
+        if pieces > max {
+            max = pieces;
+        }
+        i += 1;
+    }
+    max
+};
+
+fn material(board: &Board, side: Color) -> Material {
+    let mut material = [0; NUM_PIECES - 1];
+    for (count, &piece) in material.iter_mut().zip(ALL_PIECES.iter()) {
+        *count = (board.pieces(piece) & board.color_combined(side)).popcnt() as u8;
+    }
+    material
+}
+
+///Finds the specialized endgame for a board,
+///along with the strong side and the endgame's evaluation.
+pub(crate) fn find_endgame(evaluator: &StandardEvaluator, board: &Board) -> Option<(&'static Endgame, Color, EndgameEval)> {
+    if board.combined().popcnt() > MAX_ENDGAME_PIECES {
+        return None;
+    }
+    let white = material(board, Color::White);
+    let black = material(board, Color::Black);
+    for endgame in ENDGAMES {
+        for &(side, strong, weak) in &[(Color::White, white, black), (Color::Black, black, white)] {
+            if endgame.strong == strong && endgame.weak == weak {
+                return Some((endgame, side, (endgame.eval)(evaluator, board, side)));
+            }
+        }
+    }
+    None
+}
+
+///Rook pawns and pawns with the defending king in front of them are hard to win.
+fn rook_and_pawn_vs_rook(evaluator: &StandardEvaluator, board: &Board, strong_side: Color) -> EndgameEval {
+    let pawn = board.pieces(Piece::Pawn).to_square();
+    let weak_king = board.king_square(!strong_side);
+    let rook_pawn = matches!(pawn.get_file(), File::A | File::H);
+    let blocked = weak_king.get_file() == pawn.get_file() &&
+        ranks_ahead(strong_side, pawn.get_rank()) & BitBoard::from_square(weak_king) != EMPTY;
+    if rook_pawn || blocked {
+        EndgameEval::Scale(evaluator.drawish_scaling.rook_and_pawn)
+    } else {
+        EndgameEval::Scale(DrawishScaling::FULL)
+    }
+}
+
+///A rook or bishop pawn on the seventh rank supported by its king
+///draws against a lone queen whose king is too far away to help.
+fn queen_vs_pawn(evaluator: &StandardEvaluator, board: &Board, strong_side: Color) -> EndgameEval {
+    let weak_side = !strong_side;
+    let pawn = board.pieces(Piece::Pawn).to_square();
+    let weak_king = board.king_square(weak_side);
+    let strong_king = board.king_square(strong_side);
+    let on_seventh = pawn.get_rank() == weak_side.to_seventh_rank();
+    let drawing_file = matches!(pawn.get_file(), File::A | File::C | File::F | File::H);
+    let supported = get_king_moves(weak_king) & BitBoard::from_square(pawn) != EMPTY;
+    let king_distance = (strong_king.get_file().to_index() as i32 - pawn.get_file().to_index() as i32).abs()
+        .max((strong_king.get_rank().to_index() as i32 - pawn.get_rank().to_index() as i32).abs());
+    if on_seventh && drawing_file && supported && king_distance > 3 {
+        EndgameEval::Scale(evaluator.drawish_scaling.queen_vs_pawn)
+    } else {
+        EndgameEval::Scale(DrawishScaling::FULL)
+    }
+}
@@ -0,0 +1,1531 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+use chess::*;
+
+mod endgames;
+mod blend;
+pub use endgames::*;
+pub use blend::*;
+
+///Evaluates positions statically, from the perspective of the side to move.
+pub trait Evaluator {
+    fn evaluate(&self, board: &Board) -> Eval;
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Eval(i16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EvalKind {
+    Centipawn(i16),
+    MateIn(u8),
+    MatedIn(u8)
+}
+
+impl Display for Eval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.kind().fmt(f)
+    }
+}
+
+impl Display for EvalKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            EvalKind::Centipawn(cp) => {
+                if cp < 0 {
+                    write!(f, "-")?;
+                }
+                write!(f, "{}.{}", cp.abs() / 100, cp.abs() % 100)
+            },
+            EvalKind::MateIn(m) => write!(f, "M{}", m.div_ceil(2)),
+            EvalKind::MatedIn(m) => write!(f, "-M{}", m.div_ceil(2))
+        }
+    }
+}
+
+impl Eval {
+    pub const ZERO: Self = Self(0);
+
+    pub const DRAW: Self = Self::ZERO;
+
+    pub const MAX: Self = Self(i16::MAX);
+
+    pub const MIN: Self = Self(-Self::MAX.0);
+
+    ///The largest centipawn value, just short of the mate scores.
+    pub const MAX_CP: i16 = Self::mate_in(u8::MAX).0 - 1;
+
+    pub const fn cp(centipawns: i16) -> Self {
+        Self(centipawns)
+    }
+
+    pub const fn mate_in(plies_to_mate: u8) -> Self {
+        Self(i16::MAX - plies_to_mate as i16)
+    }
+
+    pub const fn mated_in(plies_to_mate: u8) -> Self {
+        Self(-Self::mate_in(plies_to_mate).0)
+    }
+
+    ///The raw representation, for serialization.
+    pub(crate) const fn to_bits(self) -> i16 {
+        self.0
+    }
+
+    pub(crate) const fn from_bits(bits: i16) -> Self {
+        Self(bits)
+    }
+
+    ///Converts a mate score counted from the root to one counted from a node `plies` below it,
+    ///so it stays correct when reached at a different ply. Other scores are unchanged.
+    pub fn to_node_relative(self, plies: u8) -> Self {
+        match self.kind() {
+            EvalKind::MateIn(m) => Self::mate_in(m.saturating_sub(plies)),
+            EvalKind::MatedIn(m) => Self::mated_in(m.saturating_sub(plies)),
+            EvalKind::Centipawn(_) => self
+        }
+    }
+
+    ///The inverse of `to_node_relative`.
+    pub fn to_root_relative(self, plies: u8) -> Self {
+        match self.kind() {
+            EvalKind::MateIn(m) => Self::mate_in(m.saturating_add(plies)),
+            EvalKind::MatedIn(m) => Self::mated_in(m.saturating_add(plies)),
+            EvalKind::Centipawn(_) => self
+        }
+    }
+
+    pub const fn kind(self) -> EvalKind {
+        const MAX_MATE_IN: i16 = Eval::mate_in(u8::MAX).0;
+        const MIN_MATE_IN: i16 = Eval::mate_in(u8::MIN).0;
+        const MAX_MATED_IN: i16 = Eval::mated_in(u8::MAX).0;
+        const MIN_MATED_IN: i16 = Eval::mated_in(u8::MIN).0;
+        
+        match self.0 {
+            v if v >= MAX_MATE_IN => EvalKind::MateIn((MIN_MATE_IN - v) as u8),
+            v if v <= MAX_MATED_IN => EvalKind::MatedIn((v - MIN_MATED_IN) as u8),
+            v => EvalKind::Centipawn(v),
+        }
+    }
+}
+
+macro_rules! impl_math_ops {
+    ($($trait:ident,$fn:ident,$op:tt;)*) => {
+        $(
+            impl std::ops::$trait for Eval {
+                type Output = Self;
+    
+                fn $fn(self, other: Self) -> Self::Output {
+                    Self(self.0 $op other.0)
+                }
+            }
+        )*
+    };
+}
+impl_math_ops! {
+    Add, add, +;
+    Sub, sub, -;
+    Mul, mul, *;
+    Div, div, /;
+}
+
+macro_rules! impl_math_assign_ops {
+    ($($trait:ident,$fn:ident,$op:tt;)*) => {
+        $(
+            impl std::ops::$trait for Eval {
+                fn $fn(&mut self, other: Self) {
+                    self.0 $op other.0;
+                }
+            }
+        )*
+    };
+}
+impl_math_assign_ops! {
+    AddAssign, add_assign, +=;
+    SubAssign, sub_assign, -=;
+    MulAssign, mul_assign, *=;
+    DivAssign, div_assign, /=;
+}
+
+impl std::ops::Neg for Eval {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self(-self.0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PieceSquareTable(pub [[i16; 8]; 8]);
+
+impl PieceSquareTable {
+    fn key(side: Color, square: Square) -> (usize, usize) {
+        let mut rank = square.get_rank().to_index();
+        if side == Color::White {
+            rank = 7 - rank;
+        }
+        (rank, square.get_file().to_index())
+    }
+    
+    pub fn get(&self, side: Color, square: Square) -> i16 {
+        let (rank, file) = Self::key(side, square);
+        self.0[rank][file]
+    }
+
+    pub fn set(&mut self, side: Color, square: Square, value: i16) {
+        let (rank, file) = Self::key(side, square);
+        self.0[rank][file] = value;
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PieceEvalSet<T> {
+    pub pawn: T,
+    pub knight: T,
+    pub bishop: T,
+    pub rook: T,
+    pub queen: T,
+    pub king: T
+}
+
+impl<T> PieceEvalSet<T> {
+    ///Builds a set by calling `f` for each piece in `ALL_PIECES` order.
+    pub fn from_fn(mut f: impl FnMut(Piece) -> T) -> Self {
+        Self {
+            pawn: f(Piece::Pawn),
+            knight: f(Piece::Knight),
+            bishop: f(Piece::Bishop),
+            rook: f(Piece::Rook),
+            queen: f(Piece::Queen),
+            king: f(Piece::King)
+        }
+    }
+
+    pub fn get(&self, piece: Piece) -> &T {
+        match piece {
+            Piece::Pawn => &self.pawn,
+            Piece::Knight => &self.knight,
+            Piece::Bishop => &self.bishop,
+            Piece::Rook => &self.rook,
+            Piece::Queen => &self.queen,
+            Piece::King => &self.king
+        }
+    }
+}
+
+///A pair of values interpolated by game phase
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaperedValue {
+    pub midgame: i16,
+    pub endgame: i16
+}
+
+impl TaperedValue {
+    ///Interpolates between the midgame value at phase 0 and the endgame value at `StandardEvaluator::MAX_PHASE`.
+    pub fn interpolate(self, phase: u32) -> i16 {
+        interpolate(self.midgame as i32, self.endgame as i32, phase) as i16
+    }
+}
+
+fn interpolate(midgame: i32, endgame: i32, phase: u32) -> i32 {
+    let phase = phase as i32;
+    const MAX_PHASE: i32 = StandardEvaluator::MAX_PHASE as i32;
+    (midgame * (MAX_PHASE - phase) + endgame * phase) / MAX_PHASE
+}
+
+///Clamps a value summed in `i32` to the centipawn range of `Eval`.
+fn clamp_cp(value: i32) -> i16 {
+    value.clamp(-Eval::MAX_CP as i32, Eval::MAX_CP as i32) as i16
+}
+
+fn saturate(value: i32) -> i16 {
+    value.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+///Penalties for weak pawns, per pawn
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PawnStructureWeights {
+    ///Each pawn on a file beyond the first
+    pub doubled: TaperedValue,
+    ///Pawns without friendly pawns on adjacent files
+    pub isolated: TaperedValue,
+    ///Pawns that can't be supported by friendly pawns and can't safely advance
+    pub backward: TaperedValue
+}
+
+impl Default for PawnStructureWeights {
+    fn default() -> Self {
+        EVALUATOR.pawn_structure.clone()
+    }
+}
+
+///Bonuses for each enemy piece under threat
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThreatWeights {
+    ///A knight, bishop, rook or queen attacked by a pawn
+    pub pawn_attacks_piece: TaperedValue,
+    ///A rook or queen attacked by a knight or bishop
+    pub minor_attacks_major: TaperedValue,
+    ///A knight, bishop, rook or queen that's attacked and not defended
+    pub hanging: TaperedValue
+}
+
+impl Default for ThreatWeights {
+    fn default() -> Self {
+        EVALUATOR.threats.clone()
+    }
+}
+
+///How much of the evaluation is kept in endgames where the side that's ahead often can't win,
+///in `DrawishScaling::FULL`ths.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DrawishScaling {
+    ///Each side has only a bishop besides pawns, on opposite colored squares,
+    ///and the side that's ahead has at most `OPPOSITE_BISHOPS_PAWN_SURPLUS` more pawns
+    pub opposite_bishops: i16,
+    ///Rook and pawn against a rook, with a rook pawn or the defending king in front of the pawn
+    pub rook_and_pawn: i16,
+    ///Rook pawns on one file with no bishop or a bishop that doesn't cover
+    ///the promotion square, while the defending king holds the corner
+    pub wrong_bishop: i16,
+    ///The side that's ahead has no pawns and at most a minor piece's worth of extra material
+    pub no_pawns: i16,
+    ///Queen against a rook or bishop pawn on its seventh rank, supported by its king
+    pub queen_vs_pawn: i16
+}
+
+impl DrawishScaling {
+    pub const FULL: i16 = 64;
+    ///With more extra pawns than this, opposite colored bishops are usually still winning.
+    pub const OPPOSITE_BISHOPS_PAWN_SURPLUS: u32 = 2;
+}
+
+impl Default for DrawishScaling {
+    fn default() -> Self {
+        EVALUATOR.drawish_scaling.clone()
+    }
+}
+
+///Bonuses related to passed pawns, which no enemy pawn can stop
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PassedPawnWeights {
+    ///A rook behind a passed pawn of either color on the same file, with nothing in between
+    pub rook_behind: TaperedValue
+}
+
+impl Default for PassedPawnWeights {
+    fn default() -> Self {
+        EVALUATOR.passed_pawns.clone()
+    }
+}
+
+///Bonuses for rooks and queens on the enemy's second rank,
+///when it holds enemy pawns or the enemy king is confined to its back rank
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SeventhRankWeights {
+    pub rook: TaperedValue,
+    pub queen: TaperedValue
+}
+
+impl Default for SeventhRankWeights {
+    fn default() -> Self {
+        EVALUATOR.seventh_rank.clone()
+    }
+}
+
+///Penalties for a king on a file without its own pawns to shield it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KingFileWeights {
+    ///No pawns of either color on the file
+    pub open: TaperedValue,
+    ///Only enemy pawns on the file
+    pub semi_open: TaperedValue
+}
+
+impl Default for KingFileWeights {
+    fn default() -> Self {
+        EVALUATOR.king_file.clone()
+    }
+}
+
+///Penalties for each bishop per own pawn on the bishop's square color
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BadBishopWeights {
+    pub same_color_pawn: TaperedValue,
+    ///Added when the pawn is also blocked by a piece in front of it
+    pub blocked_pawn: TaperedValue
+}
+
+impl Default for BadBishopWeights {
+    fn default() -> Self {
+        EVALUATOR.bad_bishop.clone()
+    }
+}
+
+///Bonuses for pushing the enemy king around in won endgames without enemy pawns,
+///where piece square tables alone don't make progress
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MopUpWeights {
+    ///The non-pawn material advantage needed before the bonuses apply
+    pub min_advantage: i16,
+    ///Per step of Manhattan distance between the enemy king and the center
+    pub enemy_king_edge: i16,
+    ///Per step the kings are closer than the maximum Manhattan distance
+    pub king_proximity: i16,
+    ///With only a bishop and knight against a bare king, replaces the edge bonus.
+    ///Per step the enemy king is closer to a corner of the bishop's color than the maximum Manhattan distance.
+    pub bishop_corner: i16
+}
+
+impl Default for MopUpWeights {
+    fn default() -> Self {
+        EVALUATOR.mop_up.clone()
+    }
+}
+
+///Scaling of the evaluation towards zero as the fifty move rule approaches,
+///so winning positions are converted instead of shuffled into a draw
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HalfmoveScaling {
+    ///The halfmove clock at which scaling starts.
+    ///The evaluation then shrinks linearly until it reaches zero at 100 halfmoves.
+    pub start: i16
+}
+
+impl Default for HalfmoveScaling {
+    fn default() -> Self {
+        EVALUATOR.halfmove_scaling.clone()
+    }
+}
+
+fn default_endgame_piece_values() -> PieceEvalSet<i16> {
+    EVALUATOR.endgame_piece_values.clone()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StandardEvaluator {
+    ///Midgame piece values. These are also the values used outside the evaluation,
+    ///such as for static exchange evaluation.
+    pub piece_values: PieceEvalSet<i16>,
+    #[serde(default = "default_endgame_piece_values")]
+    pub endgame_piece_values: PieceEvalSet<i16>,
+    pub midgame_piece_tables: PieceEvalSet<PieceSquareTable>,
+    pub endgame_piece_tables: PieceEvalSet<PieceSquareTable>,
+    #[serde(default)]
+    pub pawn_structure: PawnStructureWeights,
+    #[serde(default)]
+    pub drawish_scaling: DrawishScaling,
+    #[serde(default)]
+    pub threats: ThreatWeights,
+    #[serde(default)]
+    pub halfmove_scaling: HalfmoveScaling,
+    #[serde(default)]
+    pub mop_up: MopUpWeights,
+    #[serde(default)]
+    pub passed_pawns: PassedPawnWeights,
+    #[serde(default)]
+    pub seventh_rank: SeventhRankWeights,
+    #[serde(default)]
+    pub king_file: KingFileWeights,
+    #[serde(default)]
+    pub bad_bishop: BadBishopWeights,
+    ///Skips the threat and king file terms, which are expensive and matter little in endgames.
+    ///Set by the search in endgames, and never saved.
+    #[serde(skip)]
+    pub light: bool
+}
+
+impl Default for StandardEvaluator {
+    fn default() -> Self {
+        EVALUATOR.clone()
+    }
+}
+
+impl Evaluator for StandardEvaluator {
+    fn evaluate(&self, board: &Board) -> Eval {
+        StandardEvaluator::evaluate(self, board)
+    }
+}
+
+///Running material and piece square table sums for each side,
+///so they can be updated move by move instead of recomputed at every evaluation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvalState {
+    midgame: [i32; NUM_COLORS],
+    endgame: [i32; NUM_COLORS]
+}
+
+impl StandardEvaluator {
+    pub fn evaluate(&self, board: &Board) -> Eval {
+        self.evaluate_with_state(board, &self.eval_state(board))
+    }
+
+    ///Evaluates a board using the state tracked for it.
+    pub fn evaluate_with_state(&self, board: &Board, state: &EvalState) -> Eval {
+        let phase = Self::game_phase(board);
+        let us = self.evaluate_for_side(board, state, board.side_to_move(), phase);
+        let them = self.evaluate_for_side(board, state, !board.side_to_move(), phase);
+        Eval::cp(self.scale(board, clamp_cp(us - them)))
+    }
+
+    ///Scales a static evaluation towards zero as the halfmove clock approaches the fifty move rule.
+    pub fn scale_for_halfmove_clock(&self, value: Eval, halfmove_clock: u8) -> Eval {
+        let start = self.halfmove_scaling.start as i32;
+        let halfmove_clock = halfmove_clock as i32;
+        if halfmove_clock <= start {
+            return value;
+        }
+        let remaining = (100 - halfmove_clock).max(0);
+        Eval::cp((value.to_bits() as i32 * remaining / (100 - start)) as i16)
+    }
+
+    ///How the evaluation for the side to move is adjusted in endgames,
+    ///along with the name of the specialized endgame if there is one.
+    fn endgame_adjustment(&self, board: &Board, value: i16) -> (Option<&'static str>, EndgameEval) {
+        if let Some((endgame, strong_side, eval)) = find_endgame(self, board) {
+            let eval = match eval {
+                EndgameEval::Exact(eval) if strong_side != board.side_to_move() => EndgameEval::Exact(-eval),
+                eval => eval
+            };
+            return (Some(endgame.name), eval);
+        }
+        let strong_side = if value >= 0 {
+            board.side_to_move()
+        } else {
+            !board.side_to_move()
+        };
+        (None, EndgameEval::Scale(self.drawish_scale(board, strong_side)))
+    }
+
+    ///Adjusts a value for the side to move in endgames, scaling it towards zero in drawish ones.
+    fn scale(&self, board: &Board, value: i16) -> i16 {
+        match self.endgame_adjustment(board, value).1 {
+            EndgameEval::Scale(scale) => (value as i32 * scale as i32 / DrawishScaling::FULL as i32) as i16,
+            EndgameEval::Exact(eval) => eval.to_bits()
+        }
+    }
+
+    ///Computes the state for a board from scratch.
+    pub fn eval_state(&self, board: &Board) -> EvalState {
+        let mut state = EvalState::default();
+        for &side in &ALL_COLORS {
+            for &piece in &ALL_PIECES {
+                for square in *board.color_combined(side) & *board.pieces(piece) {
+                    self.add_piece(&mut state, piece, side, square, 1);
+                }
+            }
+        }
+        state
+    }
+
+    ///The state after playing `mv` on `board`, given the state for `board`.
+    pub fn update_eval_state(&self, state: &EvalState, board: &Board, mv: ChessMove) -> EvalState {
+        let mut state = *state;
+        let side = board.side_to_move();
+        let (source, dest) = (mv.get_source(), mv.get_dest());
+        let piece = board.piece_on(source).unwrap();
+        self.add_piece(&mut state, piece, side, source, -1);
+        self.add_piece(&mut state, mv.get_promotion().unwrap_or(piece), side, dest, 1);
+        if let Some(captured) = board.piece_on(dest) {
+            self.add_piece(&mut state, captured, !side, dest, -1);
+        } else if piece == Piece::Pawn && source.get_file() != dest.get_file() {
+            //En passant
+            let captured = Square::make_square(source.get_rank(), dest.get_file());
+            self.add_piece(&mut state, Piece::Pawn, !side, captured, -1);
+        }
+        if piece == Piece::King {
+            //Castling moves the rook too
+            let rook_files = match (source.get_file(), dest.get_file()) {
+                (File::E, File::G) => Some((File::H, File::F)),
+                (File::E, File::C) => Some((File::A, File::D)),
+                _ => None
+            };
+            if let Some((rook_source, rook_dest)) = rook_files {
+                let rank = source.get_rank();
+                self.add_piece(&mut state, Piece::Rook, side, Square::make_square(rank, rook_source), -1);
+                self.add_piece(&mut state, Piece::Rook, side, Square::make_square(rank, rook_dest), 1);
+            }
+        }
+        state
+    }
+
+    pub fn piece_value(&self, piece: Piece) -> Eval {
+        Eval::cp(*self.piece_values.get(piece))
+    }
+}
+
+#[derive(Debug)]
+pub enum EvalFileError {
+    Io(io::Error),
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    InvalidLength(usize),
+    ChecksumMismatch,
+    ///The file has this many more terms than this version knows about
+    UnknownTerms(usize),
+    ///A term is outside the range the evaluation can handle
+    InvalidValue {
+        term: String,
+        value: i16
+    }
+}
+
+impl Display for EvalFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::InvalidMagic => write!(f, "not an evaluator file"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported evaluator file version {}", version),
+            Self::InvalidLength(length) => write!(f, "evaluator file has invalid length {}", length),
+            Self::ChecksumMismatch => write!(f, "evaluator file checksum mismatch"),
+            Self::UnknownTerms(count) => write!(f, "evaluator file has {} unknown terms", count),
+            Self::InvalidValue { term, value } => write!(f, "evaluator file has invalid {}: {}", term, value)
+        }
+    }
+}
+
+impl std::error::Error for EvalFileError {}
+
+impl From<io::Error> for EvalFileError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+//Binary evaluator format:
+//Magic, version, then every value as a little endian i16:
+//midgame piece values, then midgame and endgame piece square tables, each in piece order.
+//Since version 2, those are followed by the number of extra terms as a little endian u16
+//and the extra terms in `extra_terms_mut` order. Terms the file doesn't have keep their defaults.
+//Ends with the FNV-1a hash of everything before it as a little endian u32.
+const EVAL_FILE_MAGIC: &[u8; 4] = b"LNEV";
+const EVAL_FILE_VERSION: u8 = 2;
+const EVAL_FILE_VALUES: usize = NUM_PIECES * (1 + 2 * NUM_SQUARES);
+const EVAL_FILE_HEADER_LENGTH: usize = EVAL_FILE_MAGIC.len() + 1;
+const EVAL_FILE_V1_LENGTH: usize = EVAL_FILE_HEADER_LENGTH + EVAL_FILE_VALUES * 2 + 4;
+
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash, &byte| (hash ^ byte as u32).wrapping_mul(0x01000193))
+}
+
+impl StandardEvaluator {
+    ///Every term outside the piece values and tables, in binary format order.
+    ///New terms go at the end so older files stay readable.
+    fn extra_terms_mut(&mut self) -> Vec<&mut i16> {
+        let pawn_structure = &mut self.pawn_structure;
+        let mut terms = Vec::new();
+        for value in [
+            &mut pawn_structure.doubled,
+            &mut pawn_structure.isolated,
+            &mut pawn_structure.backward
+        ] {
+            terms.push(&mut value.midgame);
+            terms.push(&mut value.endgame);
+        }
+        let DrawishScaling {
+            opposite_bishops,
+            rook_and_pawn,
+            wrong_bishop,
+            no_pawns,
+            queen_vs_pawn
+        } = &mut self.drawish_scaling;
+        terms.extend([opposite_bishops, rook_and_pawn, wrong_bishop, no_pawns]);
+        let threats = &mut self.threats;
+        for value in [
+            &mut threats.pawn_attacks_piece,
+            &mut threats.minor_attacks_major,
+            &mut threats.hanging
+        ] {
+            terms.push(&mut value.midgame);
+            terms.push(&mut value.endgame);
+        }
+        terms.push(&mut self.halfmove_scaling.start);
+        let mop_up = &mut self.mop_up;
+        terms.extend([
+            &mut mop_up.min_advantage,
+            &mut mop_up.enemy_king_edge,
+            &mut mop_up.king_proximity,
+            &mut mop_up.bishop_corner
+        ]);
+        let rook_behind = &mut self.passed_pawns.rook_behind;
+        terms.push(&mut rook_behind.midgame);
+        terms.push(&mut rook_behind.endgame);
+        let endgame_piece_values = &mut self.endgame_piece_values;
+        terms.extend([
+            &mut endgame_piece_values.pawn,
+            &mut endgame_piece_values.knight,
+            &mut endgame_piece_values.bishop,
+            &mut endgame_piece_values.rook,
+            &mut endgame_piece_values.queen,
+            &mut endgame_piece_values.king
+        ]);
+        terms.push(queen_vs_pawn);
+        for value in [
+            &mut self.seventh_rank.rook,
+            &mut self.seventh_rank.queen,
+            &mut self.king_file.open,
+            &mut self.king_file.semi_open,
+            &mut self.bad_bishop.same_color_pawn,
+            &mut self.bad_bishop.blocked_pawn
+        ] {
+            terms.push(&mut value.midgame);
+            terms.push(&mut value.endgame);
+        }
+        terms
+    }
+
+    ///The binary format without the trailing checksum.
+    fn binary_contents(&self) -> Vec<u8> {
+        let mut extra_terms = self.clone();
+        let extra_terms = extra_terms.extra_terms_mut();
+        let mut bytes = Vec::with_capacity(EVAL_FILE_V1_LENGTH + 2 + extra_terms.len() * 2);
+        bytes.extend_from_slice(EVAL_FILE_MAGIC);
+        bytes.push(EVAL_FILE_VERSION);
+        let mut values = Vec::with_capacity(EVAL_FILE_VALUES);
+        for &piece in &ALL_PIECES {
+            values.push(*self.piece_values.get(piece));
+        }
+        for tables in [&self.midgame_piece_tables, &self.endgame_piece_tables] {
+            for &piece in &ALL_PIECES {
+                values.extend(tables.get(piece).0.iter().flatten());
+            }
+        }
+        values.extend(extra_terms.iter().map(|value| **value));
+        for (index, value) in values.into_iter().enumerate() {
+            if index == EVAL_FILE_VALUES {
+                bytes.extend_from_slice(&(extra_terms.len() as u16).to_le_bytes());
+            }
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    ///The checksum of the evaluator's weights, as stored at the end of its binary file.
+    ///Identifies which weights are in use without printing all of them.
+    pub fn checksum(&self) -> u32 {
+        fnv1a(&self.binary_contents())
+    }
+
+    ///Writes the evaluator in the compact binary format.
+    ///The serde representation remains the human-editable one.
+    pub fn write_binary(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut bytes = self.binary_contents();
+        let checksum = fnv1a(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        writer.write_all(&bytes)
+    }
+
+    ///Reads an evaluator written by `write_binary`.
+    pub fn read_binary(reader: &mut impl Read) -> Result<Self, EvalFileError> {
+        let mut bytes = Vec::with_capacity(EVAL_FILE_V1_LENGTH);
+        reader.read_to_end(&mut bytes)?;
+        if !bytes.starts_with(EVAL_FILE_MAGIC) {
+            return Err(EvalFileError::InvalidMagic);
+        }
+        let version = bytes.get(EVAL_FILE_MAGIC.len()).copied().unwrap_or_default();
+        if version == 0 || version > EVAL_FILE_VERSION {
+            return Err(EvalFileError::UnsupportedVersion(version));
+        }
+        let extra_terms_count = if version >= 2 {
+            let offset = EVAL_FILE_HEADER_LENGTH + EVAL_FILE_VALUES * 2;
+            match bytes.get(offset..offset + 2) {
+                Some(count) => Some(u16::from_le_bytes([count[0], count[1]]) as usize),
+                None => return Err(EvalFileError::InvalidLength(bytes.len()))
+            }
+        } else {
+            None
+        };
+        let length = match extra_terms_count {
+            Some(count) => EVAL_FILE_V1_LENGTH + 2 + count * 2,
+            None => EVAL_FILE_V1_LENGTH
+        };
+        if bytes.len() != length {
+            return Err(EvalFileError::InvalidLength(bytes.len()));
+        }
+        let (contents, checksum) = bytes.split_at(length - 4);
+        if fnv1a(contents) != u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) {
+            return Err(EvalFileError::ChecksumMismatch);
+        }
+
+        let mut values = contents[EVAL_FILE_HEADER_LENGTH..]
+            .chunks_exact(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]));
+        let mut next = || values.next().unwrap();
+        let piece_values = PieceEvalSet::from_fn(|_| next());
+        let mut read_tables = || PieceEvalSet::from_fn(|_| {
+            let mut table = PieceSquareTable([[0; 8]; 8]);
+            for value in table.0.iter_mut().flatten() {
+                *value = next();
+            }
+            table
+        });
+        let midgame_piece_tables = read_tables();
+        let endgame_piece_tables = read_tables();
+        let mut evaluator = Self {
+            piece_values,
+            midgame_piece_tables,
+            endgame_piece_tables,
+            ..Self::default()
+        };
+        if let Some(count) = extra_terms_count {
+            //Skip the count itself
+            next();
+            let mut extra_terms = evaluator.extra_terms_mut();
+            if count > extra_terms.len() {
+                return Err(EvalFileError::UnknownTerms(count - extra_terms.len()));
+            }
+            for term in extra_terms.iter_mut().take(count) {
+                **term = next();
+            }
+        }
+        Ok(evaluator)
+    }
+
+    ///Loads an evaluator written by `write_binary` from a file and validates it.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, EvalFileError> {
+        let file = fs::File::open(path)?;
+        let evaluator = Self::read_binary(&mut BufReader::new(file))?;
+        evaluator.validate()?;
+        Ok(evaluator)
+    }
+
+    ///Checks that every term is in a range that can't overflow the evaluation
+    ///or break assumptions elsewhere, such as pieces being worth something.
+    pub fn validate(&self) -> Result<(), EvalFileError> {
+        let check = |term: String, value: i16, range: std::ops::RangeInclusive<i16>| {
+            if range.contains(&value) {
+                Ok(())
+            } else {
+                Err(EvalFileError::InvalidValue { term, value })
+            }
+        };
+        for &piece in &ALL_PIECES {
+            let range = if piece == Piece::King { 0..=0 } else { 1..=2000 };
+            check(format!("midgame {:?} value", piece), *self.piece_values.get(piece), range.clone())?;
+            check(format!("endgame {:?} value", piece), *self.endgame_piece_values.get(piece), range)?;
+            for (phase, tables) in [("midgame", &self.midgame_piece_tables), ("endgame", &self.endgame_piece_tables)] {
+                for &square in &ALL_SQUARES {
+                    let value = tables.get(piece).get(Color::White, square);
+                    check(format!("{} {:?} table value on {}", phase, piece, square), value, -500..=500)?;
+                }
+            }
+        }
+        let pawn_structure = &self.pawn_structure;
+        for (name, value) in [
+            ("doubled", pawn_structure.doubled),
+            ("isolated", pawn_structure.isolated),
+            ("backward", pawn_structure.backward)
+        ] {
+            check(format!("midgame {} pawn penalty", name), value.midgame, -500..=500)?;
+            check(format!("endgame {} pawn penalty", name), value.endgame, -500..=500)?;
+        }
+        let threats = &self.threats;
+        for (name, value) in [
+            ("pawn threat", threats.pawn_attacks_piece),
+            ("minor piece threat", threats.minor_attacks_major),
+            ("hanging piece", threats.hanging)
+        ] {
+            check(format!("midgame {} bonus", name), value.midgame, -500..=500)?;
+            check(format!("endgame {} bonus", name), value.endgame, -500..=500)?;
+        }
+        let scaling = &self.drawish_scaling;
+        for (name, value) in [
+            ("opposite bishops", scaling.opposite_bishops),
+            ("rook and pawn", scaling.rook_and_pawn),
+            ("wrong bishop", scaling.wrong_bishop),
+            ("no pawns", scaling.no_pawns),
+            ("queen against pawn", scaling.queen_vs_pawn)
+        ] {
+            check(format!("{} scale", name), value, 0..=DrawishScaling::FULL)?;
+        }
+        check("halfmove scaling start".to_owned(), self.halfmove_scaling.start, 0..=99)?;
+        check("mop-up minimum advantage".to_owned(), self.mop_up.min_advantage, 0..=4000)?;
+        check("mop-up king edge bonus".to_owned(), self.mop_up.enemy_king_edge, -100..=100)?;
+        check("mop-up king proximity bonus".to_owned(), self.mop_up.king_proximity, -100..=100)?;
+        check("mop-up bishop corner bonus".to_owned(), self.mop_up.bishop_corner, -100..=100)?;
+        let rook_behind = self.passed_pawns.rook_behind;
+        check("midgame rook behind passed pawn bonus".to_owned(), rook_behind.midgame, -500..=500)?;
+        check("endgame rook behind passed pawn bonus".to_owned(), rook_behind.endgame, -500..=500)?;
+        for (name, value) in [
+            ("rook on seventh rank bonus", self.seventh_rank.rook),
+            ("queen on seventh rank bonus", self.seventh_rank.queen),
+            ("king on open file penalty", self.king_file.open),
+            ("king on semi-open file penalty", self.king_file.semi_open),
+            ("bad bishop pawn penalty", self.bad_bishop.same_color_pawn),
+            ("bad bishop blocked pawn penalty", self.bad_bishop.blocked_pawn)
+        ] {
+            check(format!("midgame {}", name), value.midgame, -500..=500)?;
+            check(format!("endgame {}", name), value.endgame, -500..=500)?;
+        }
+        Ok(())
+    }
+}
+
+impl StandardEvaluator {
+    pub const MAX_PHASE: u32 = 256;
+
+    fn game_phase(board: &Board) -> u32 {
+        macro_rules! game_phase_fn {
+            ($($piece:ident=$weight:expr,$count:expr;)*) => {
+                const INIT_PHASE: u32 = (0 $( + $count * $weight)*) * 2;
+                let inv_phase = 0 $( + board.pieces(Piece::$piece).popcnt() * $weight)*;
+                let phase = INIT_PHASE.saturating_sub(inv_phase); //Early promotions
+                (phase * Self::MAX_PHASE + (INIT_PHASE / 2)) / INIT_PHASE
+            }
+        }
+        game_phase_fn! {
+            Pawn   = 0, 8;
+            Knight = 1, 2;
+            Bishop = 1, 2;
+            Rook   = 2, 2;
+            Queen  = 4, 1;
+        }
+    }
+
+    fn add_piece(&self, state: &mut EvalState, piece: Piece, side: Color, square: Square, sign: i32) {
+        let midgame = *self.piece_values.get(piece) as i32 + self.midgame_piece_tables.get(piece).get(side, square) as i32;
+        let endgame = *self.endgame_piece_values.get(piece) as i32 + self.endgame_piece_tables.get(piece).get(side, square) as i32;
+        state.midgame[side.to_index()] += sign * midgame;
+        state.endgame[side.to_index()] += sign * endgame;
+    }
+
+    ///Midgame and endgame penalties for the side's doubled, isolated and backward pawns.
+    fn pawn_structure_penalty(&self, board: &Board, side: Color) -> (i32, i32) {
+        let weights = &self.pawn_structure;
+        let pawns = *board.pieces(Piece::Pawn) & *board.color_combined(side);
+        let enemy_pawns = *board.pieces(Piece::Pawn) & *board.color_combined(!side);
+        let mut penalty = (0, 0);
+        let mut add = |value: TaperedValue, count: i32| {
+            penalty.0 += value.midgame as i32 * count;
+            penalty.1 += value.endgame as i32 * count;
+        };
+        for &file in &ALL_FILES {
+            let count = (pawns & get_file(file)).popcnt() as i32;
+            if count > 1 {
+                add(weights.doubled, count - 1);
+            }
+        }
+        for square in pawns {
+            let adjacent_pawns = pawns & get_adjacent_files(square.get_file());
+            if adjacent_pawns == EMPTY {
+                add(weights.isolated, 1);
+                continue;
+            }
+            //Every friendly pawn on the adjacent files is ahead of this one,
+            //and an enemy pawn guards the square in front of it.
+            let supporters = !ranks_ahead(side, square.get_rank());
+            let stop_guarded = square
+                .forward(side)
+                .map(|stop| get_pawn_attacks(stop, side, enemy_pawns) != EMPTY)
+                .unwrap_or_default();
+            if adjacent_pawns & supporters == EMPTY && stop_guarded {
+                add(weights.backward, 1);
+            }
+        }
+        penalty
+    }
+
+    fn non_pawn_material(&self, board: &Board, side: Color) -> i16 {
+        [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+            .iter()
+            .map(|&piece| {
+                let pieces = *board.pieces(piece) & *board.color_combined(side);
+                pieces.popcnt() as i16 * *self.piece_values.get(piece)
+            })
+            .sum()
+    }
+
+    ///Midgame and endgame bonuses for the side's rooks behind passed pawns of either color.
+    fn passed_pawn_bonus(&self, board: &Board, side: Color) -> (i32, i32) {
+        let weights = &self.passed_pawns;
+        let rooks = *board.pieces(Piece::Rook) & *board.color_combined(side);
+        if rooks == EMPTY {
+            return (0, 0);
+        }
+        let pawns = *board.pieces(Piece::Pawn);
+        let mut count = 0;
+        for &color in &ALL_COLORS {
+            let enemy_pawns = pawns & *board.color_combined(!color);
+            for pawn in pawns & *board.color_combined(color) {
+                let file = get_file(pawn.get_file());
+                let front_span = (file | get_adjacent_files(pawn.get_file())) & ranks_ahead(color, pawn.get_rank());
+                if enemy_pawns & front_span != EMPTY {
+                    continue;
+                }
+                let behind = file & !ranks_ahead(color, pawn.get_rank()) & !BitBoard::from_square(pawn);
+                for rook in rooks & behind {
+                    if between(rook, pawn) & *board.combined() == EMPTY {
+                        count += 1;
+                    }
+                }
+            }
+        }
+        (weights.rook_behind.midgame as i32 * count, weights.rook_behind.endgame as i32 * count)
+    }
+
+    ///Midgame and endgame bonuses for the side's rooks and queens on the enemy's second rank.
+    fn seventh_rank_bonus(&self, board: &Board, side: Color) -> (i32, i32) {
+        let weights = &self.seventh_rank;
+        let seventh = get_rank(side.to_seventh_rank());
+        let enemy = *board.color_combined(!side);
+        let king_confined = board.king_square(!side).get_rank() == side.to_their_backrank();
+        if !king_confined && enemy & *board.pieces(Piece::Pawn) & seventh == EMPTY {
+            return (0, 0);
+        }
+        let ally = *board.color_combined(side) & seventh;
+        let rooks = (ally & *board.pieces(Piece::Rook)).popcnt() as i32;
+        let queens = (ally & *board.pieces(Piece::Queen)).popcnt() as i32;
+        (
+            weights.rook.midgame as i32 * rooks + weights.queen.midgame as i32 * queens,
+            weights.rook.endgame as i32 * rooks + weights.queen.endgame as i32 * queens
+        )
+    }
+
+    ///Midgame and endgame penalties for the side's bishops hemmed in by their own pawns.
+    fn bad_bishop_penalty(&self, board: &Board, side: Color) -> (i32, i32) {
+        let weights = &self.bad_bishop;
+        let ally = *board.color_combined(side);
+        let pawns = ally & *board.pieces(Piece::Pawn);
+        let blocked = pawns & BitBoard(match side {
+            Color::White => board.combined().0 >> 8,
+            Color::Black => board.combined().0 << 8
+        });
+        let mut penalty = (0, 0);
+        for bishop in ally & *board.pieces(Piece::Bishop) {
+            let color = if BitBoard::from_square(bishop) & DARK_SQUARES != EMPTY {
+                DARK_SQUARES
+            } else {
+                !DARK_SQUARES
+            };
+            let same_color = (pawns & color).popcnt() as i32;
+            let blocked = (blocked & color).popcnt() as i32;
+            penalty.0 += weights.same_color_pawn.midgame as i32 * same_color + weights.blocked_pawn.midgame as i32 * blocked;
+            penalty.1 += weights.same_color_pawn.endgame as i32 * same_color + weights.blocked_pawn.endgame as i32 * blocked;
+        }
+        penalty
+    }
+
+    ///Midgame and endgame penalties for the side's king on an open or semi-open file.
+    fn king_file_penalty(&self, board: &Board, side: Color) -> (i32, i32) {
+        let weights = &self.king_file;
+        let file = get_file(board.king_square(side).get_file());
+        let pawns = *board.pieces(Piece::Pawn) & file;
+        if pawns == EMPTY {
+            (weights.open.midgame as i32, weights.open.endgame as i32)
+        } else if pawns & *board.color_combined(side) == EMPTY {
+            (weights.semi_open.midgame as i32, weights.semi_open.endgame as i32)
+        } else {
+            (0, 0)
+        }
+    }
+
+    ///Drives the enemy king to the edge and brings the side's king closer
+    ///when the side is winning on material and the enemy has no pawns left.
+    fn mop_up_bonus(&self, board: &Board, side: Color) -> i16 {
+        let weights = &self.mop_up;
+        let enemy_pawns = *board.pieces(Piece::Pawn) & *board.color_combined(!side);
+        let advantage = self.non_pawn_material(board, side) - self.non_pawn_material(board, !side);
+        if enemy_pawns != EMPTY || advantage < weights.min_advantage {
+            return 0;
+        }
+        let king = board.king_square(side);
+        let enemy_king = board.king_square(!side);
+        let distance = |a: Square, b: Square| {
+            (a.get_file().to_index() as i16 - b.get_file().to_index() as i16).abs() +
+            (a.get_rank().to_index() as i16 - b.get_rank().to_index() as i16).abs()
+        };
+        let proximity = weights.king_proximity * (14 - distance(king, enemy_king));
+
+        //KBNvK can only be mated in a corner the bishop covers.
+        let ally = *board.color_combined(side);
+        let bishops = ally & *board.pieces(Piece::Bishop);
+        let knights = ally & *board.pieces(Piece::Knight);
+        let bishop_and_knight = bishops.popcnt() == 1 && knights.popcnt() == 1 && ally.popcnt() == 3;
+        if bishop_and_knight && board.color_combined(!side).popcnt() == 1 {
+            let corners = if bishops & DARK_SQUARES != EMPTY {
+                [Square::A1, Square::H8]
+            } else {
+                [Square::A8, Square::H1]
+            };
+            let corner_distance = corners.iter().map(|&corner| distance(enemy_king, corner)).min().unwrap();
+            return weights.bishop_corner * (14 - corner_distance) + proximity;
+        }
+
+        let center_distance = |index: usize| (3 - index as i16).max(index as i16 - 4);
+        let edge_closeness = center_distance(enemy_king.get_file().to_index()) +
+            center_distance(enemy_king.get_rank().to_index());
+        weights.enemy_king_edge * edge_closeness + proximity
+    }
+
+    ///The generic drawish scale factor for the side that's ahead,
+    ///or `DrawishScaling::FULL` if the position isn't drawish.
+    ///Specific material configurations are handled by `ENDGAMES` instead.
+    fn drawish_scale(&self, board: &Board, strong_side: Color) -> i16 {
+        let scaling = &self.drawish_scaling;
+        let strong = *board.color_combined(strong_side);
+        let weak = *board.color_combined(!strong_side);
+        let pawns = *board.pieces(Piece::Pawn);
+        let bishops = *board.pieces(Piece::Bishop);
+        let knights = *board.pieces(Piece::Knight);
+        let rooks = *board.pieces(Piece::Rook);
+        let queens = *board.pieces(Piece::Queen);
+        let strong_material = self.non_pawn_material(board, strong_side);
+        let weak_material = self.non_pawn_material(board, !strong_side);
+        let weak_king = board.king_square(!strong_side);
+
+        if strong & pawns == EMPTY {
+            //Two knights can't force mate either.
+            let only_knights = strong & (bishops | rooks | queens) == EMPTY && weak & pawns == EMPTY;
+            if strong_material - weak_material <= self.piece_values.bishop || only_knights {
+                return scaling.no_pawns;
+            }
+        }
+
+        if knights | rooks | queens == EMPTY &&
+            (strong & bishops).popcnt() == 1 && (weak & bishops).popcnt() == 1 &&
+            (bishops & DARK_SQUARES).popcnt() == 1 &&
+            (strong & pawns).popcnt() <= (weak & pawns).popcnt() + DrawishScaling::OPPOSITE_BISHOPS_PAWN_SURPLUS {
+            return scaling.opposite_bishops;
+        }
+
+        let strong_pawns = strong & pawns;
+        let rook_file = [File::A, File::H]
+            .iter()
+            .copied()
+            .find(|&file| strong_pawns != EMPTY && strong_pawns & !get_file(file) == EMPTY);
+        if let Some(file) = rook_file {
+            let strong_bishops = strong & bishops;
+            if strong & (knights | rooks | queens) == EMPTY && strong_bishops.popcnt() <= 1 && weak_material == 0 {
+                let promotion_square = Square::make_square(strong_side.to_their_backrank(), file);
+                let dark_promotion = BitBoard::from_square(promotion_square) & DARK_SQUARES != EMPTY;
+                let wrong_bishop = strong_bishops == EMPTY ||
+                    (strong_bishops & DARK_SQUARES != EMPTY) != dark_promotion;
+                let corner = get_king_moves(promotion_square) | BitBoard::from_square(promotion_square);
+                if wrong_bishop && corner & BitBoard::from_square(weak_king) != EMPTY {
+                    return scaling.wrong_bishop;
+                }
+            }
+        }
+
+        DrawishScaling::FULL
+    }
+
+    ///Midgame and endgame bonuses for the side's threats against enemy pieces.
+    fn threats_bonus(&self, board: &Board, side: Color) -> (i32, i32) {
+        let weights = &self.threats;
+        let ally = *board.color_combined(side);
+        let enemy = *board.color_combined(!side);
+        let minors = *board.pieces(Piece::Knight) | *board.pieces(Piece::Bishop);
+        let majors = *board.pieces(Piece::Rook) | *board.pieces(Piece::Queen);
+        let pieces = minors | majors;
+
+        let pawns = *board.pieces(Piece::Pawn);
+        let pawn_threats = pawn_attacks(board, side);
+        let minor_threats = piece_attacks(board, side, ally & minors);
+        let attacked = pawn_threats | minor_threats | piece_attacks(board, side, ally & !minors & !pawns);
+        let defended = pawn_attacks(board, !side) | piece_attacks(board, !side, enemy & !pawns);
+
+        let mut bonus = (0, 0);
+        let mut add = |value: TaperedValue, targets: BitBoard| {
+            let count = targets.popcnt() as i32;
+            bonus.0 += value.midgame as i32 * count;
+            bonus.1 += value.endgame as i32 * count;
+        };
+        add(weights.pawn_attacks_piece, enemy & pieces & pawn_threats);
+        add(weights.minor_attacks_major, enemy & majors & minor_threats);
+        add(weights.hanging, enemy & pieces & attacked & !defended);
+        bonus
+    }
+
+    ///Reports every term not tracked by `EvalState` to `term` as it's computed,
+    ///as midgame and endgame values.
+    fn positional_terms(&self, board: &Board, side: Color, mut term: impl FnMut(&'static str, i32, i32)) {
+        let (midgame_penalty, endgame_penalty) = self.pawn_structure_penalty(board, side);
+        term("Pawn structure", -midgame_penalty, -endgame_penalty);
+        if !self.light {
+            let (midgame, endgame) = self.threats_bonus(board, side);
+            term("Threats", midgame, endgame);
+        }
+        let (midgame, endgame) = self.passed_pawn_bonus(board, side);
+        term("Passed pawns", midgame, endgame);
+        let (midgame, endgame) = self.seventh_rank_bonus(board, side);
+        term("Seventh rank", midgame, endgame);
+        if !self.light {
+            let (midgame_penalty, endgame_penalty) = self.king_file_penalty(board, side);
+            term("King file", -midgame_penalty, -endgame_penalty);
+        }
+        let (midgame_penalty, endgame_penalty) = self.bad_bishop_penalty(board, side);
+        term("Bad bishop", -midgame_penalty, -endgame_penalty);
+        let mop_up = self.mop_up_bonus(board, side) as i32;
+        term("Mop-up", mop_up, mop_up);
+    }
+
+    fn evaluate_for_side(&self, board: &Board, state: &EvalState, side: Color, phase: u32) -> i32 {
+        let mut midgame = state.midgame[side.to_index()];
+        let mut endgame = state.endgame[side.to_index()];
+        self.positional_terms(board, side, |_, term_midgame, term_endgame| {
+            midgame += term_midgame;
+            endgame += term_endgame;
+        });
+        interpolate(midgame, endgame, phase)
+    }
+
+    ///Evaluates a board like `evaluate`, but reports every term's contribution for each side.
+    pub fn evaluate_verbose(&self, board: &Board) -> EvalTrace {
+        let mut terms: Vec<EvalTraceTerm> = Vec::new();
+        let mut add_term = |name: &'static str, side: Color, midgame: i32, endgame: i32| {
+            let index = match terms.iter().position(|term| term.name == name) {
+                Some(index) => index,
+                None => {
+                    terms.push(EvalTraceTerm {
+                        name,
+                        values: [TaperedValue::default(); NUM_COLORS]
+                    });
+                    terms.len() - 1
+                }
+            };
+            //Only the evaluation itself is exact for extreme weights
+            let term = &mut terms[index].values[side.to_index()];
+            term.midgame = saturate(term.midgame as i32 + midgame);
+            term.endgame = saturate(term.endgame as i32 + endgame);
+        };
+        for &side in &ALL_COLORS {
+            for &piece in &ALL_PIECES {
+                let pieces = *board.color_combined(side) & *board.pieces(piece);
+                let count = pieces.popcnt() as i32;
+                add_term(
+                    "Material",
+                    side,
+                    count * *self.piece_values.get(piece) as i32,
+                    count * *self.endgame_piece_values.get(piece) as i32
+                );
+                for square in pieces {
+                    add_term(
+                        "Piece squares",
+                        side,
+                        self.midgame_piece_tables.get(piece).get(side, square) as i32,
+                        self.endgame_piece_tables.get(piece).get(side, square) as i32
+                    );
+                }
+            }
+            self.positional_terms(board, side, |name, midgame, endgame| add_term(name, side, midgame, endgame));
+        }
+        let phase = Self::game_phase(board);
+        let state = self.eval_state(board);
+        let us = self.evaluate_for_side(board, &state, board.side_to_move(), phase);
+        let them = self.evaluate_for_side(board, &state, !board.side_to_move(), phase);
+        let (endgame, adjustment) = self.endgame_adjustment(board, clamp_cp(us - them));
+        EvalTrace {
+            phase,
+            side_to_move: board.side_to_move(),
+            terms,
+            endgame,
+            adjustment,
+            value: self.evaluate(board)
+        }
+    }
+}
+
+///One term's contribution to the evaluation in centipawns, for each side and before phase interpolation.
+#[derive(Debug, Clone)]
+pub struct EvalTraceTerm {
+    pub name: &'static str,
+    ///Indexed by color
+    pub values: [TaperedValue; NUM_COLORS]
+}
+
+impl EvalTraceTerm {
+    ///White's value minus black's value at the given phase.
+    pub fn net(&self, phase: u32) -> i16 {
+        self.values[Color::White.to_index()].interpolate(phase) -
+        self.values[Color::Black.to_index()].interpolate(phase)
+    }
+}
+
+///A breakdown of an evaluation by term, from `StandardEvaluator::evaluate_verbose`.
+#[derive(Debug, Clone)]
+pub struct EvalTrace {
+    ///From 0 in the opening to `StandardEvaluator::MAX_PHASE` in the endgame
+    pub phase: u32,
+    pub side_to_move: Color,
+    pub terms: Vec<EvalTraceTerm>,
+    ///The specialized endgame recognized, if any
+    pub endgame: Option<&'static str>,
+    ///How the sum of the terms was adjusted for the endgame
+    pub adjustment: EndgameEval,
+    ///The final evaluation for the side to move
+    pub value: Eval
+}
+
+impl Display for EvalTrace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:<16} | {:>6} {:>6} | {:>6} {:>6} | {:>6}", "Term", "White", "", "Black", "", "Net")?;
+        writeln!(f, "{:<16} | {:>6} {:>6} | {:>6} {:>6} | {:>6}", "", "MG", "EG", "MG", "EG", "")?;
+        writeln!(f, "{:-<16}-+-{:-<13}-+-{:-<13}-+-{:-<6}", "", "", "", "")?;
+        for term in &self.terms {
+            let [white, black] = term.values;
+            writeln!(
+                f,
+                "{:<16} | {:>6} {:>6} | {:>6} {:>6} | {:>6}",
+                term.name,
+                white.midgame,
+                white.endgame,
+                black.midgame,
+                black.endgame,
+                term.net(self.phase)
+            )?;
+        }
+        writeln!(f, "Phase: {}/{}", self.phase, StandardEvaluator::MAX_PHASE)?;
+        if let Some(endgame) = self.endgame {
+            writeln!(f, "Endgame: {}", endgame)?;
+        }
+        match self.adjustment {
+            EndgameEval::Scale(scale) => writeln!(f, "Scale: {}/{}", scale, DrawishScaling::FULL)?,
+            EndgameEval::Exact(value) => writeln!(f, "Exact: {} centipawns (side to move)", value.to_bits())?
+        }
+        let white_value = match self.side_to_move {
+            Color::White => self.value,
+            Color::Black => -self.value
+        };
+        write!(f, "Evaluation: {} centipawns (white's perspective)", white_value.to_bits())
+    }
+}
+
+pub const EVALUATOR: StandardEvaluator = StandardEvaluator {
+    piece_values: PieceEvalSet {
+        pawn: 100,
+        knight: 320,
+        bishop: 330,
+        rook: 500,
+        queen: 900,
+        king: 0,
+    },
+    endgame_piece_values: PieceEvalSet {
+        pawn: 110,
+        knight: 300,
+        bishop: 340,
+        rook: 520,
+        queen: 950,
+        king: 0,
+    },
+    midgame_piece_tables: PieceEvalSet {
+        pawn: PieceSquareTable([
+            [   0,    0,    0,    0,    0,    0,    0,    0],
+            [ 134,  126,  115,  121,  101,   79,   19,   11],
+            [ -50,   -6,   14,   21,   29,   46,    5,  -32],
+            [ -52,  -29,  -34,  -20,  -10,  -10,  -20,  -47],
+            [ -58,  -38,  -42,  -37,  -30,  -35,  -21,  -46],
+            [ -59,  -44,  -47,  -45,  -34,  -39,  -11,  -37],
+            [ -63,  -50,  -56,  -54,  -56,  -27,   -4,  -41],
+            [   0,    0,    0,    0,    0,    0,    0,    0],
+        ]),
+        knight: PieceSquareTable([
+            [-112,  -19,  -31,   -5,    0,  -22,   -4,  -36],
+            [ -25,  -28,   17,   25,   31,   54,  -22,  -14],
+            [ -33,    4,   36,   48,   83,   83,   45,   21],
+            [ -14,   -1,   19,   29,   11,   38,    8,   17],
+            [ -27,  -19,   -3,   -8,    1,   -3,   15,  -15],
+            [ -42,  -23,  -17,  -10,   -4,  -13,  -13,  -30],
+            [ -67,  -52,  -35,  -23,  -29,  -25,  -47,  -33],
+            [ -55,  -44,  -77,  -56,  -46,  -38,  -41,  -82],
+        ]),
+        bishop: PieceSquareTable([
+            [ -22,  -10,  -30,  -20,  -25,  -31,    1,  -10],
+            [ -32,   13,    5,   -3,   10,   23,    4,    4],
+            [  -2,   14,   52,   42,   58,   65,   57,   30],
+            [ -14,   20,   23,   57,   34,   48,   15,    8],
+            [ -11,    9,    9,   18,   31,    4,    8,    5],
+            [  -5,    4,    2,    8,    2,    3,    4,   10],
+            [   2,    3,    9,   -9,   -7,   -1,   16,    8],
+            [  -2,    0,  -22,  -37,  -39,  -21,  -17,   -9],
+        ]),
+        rook: PieceSquareTable([
+            [  11,   11,    6,   11,   15,   25,   21,   32],
+            [ -15,  -17,    9,   20,   17,   37,   10,   32],
+            [ -48,  -11,   -5,   12,   35,   42,   56,   21],
+            [ -59,  -43,  -28,   -7,  -21,  -15,   -1,   -8],
+            [ -81,  -81,  -73,  -67,  -72,  -63,  -38,  -44],
+            [ -93,  -79,  -85,  -82,  -84,  -83,  -33,  -62],
+            [-119,  -79,  -75,  -83,  -84,  -65,  -49,  -96],
+            [ -75,  -69,  -66,  -64,  -67,  -69,  -39,  -67],
+        ]),
+        queen: PieceSquareTable([
+            [  24,   24,   32,   52,   54,   64,   53,   79],
+            [  22,   -8,   51,   22,   59,  104,   65,   87],
+            [  27,   38,   28,   61,   79,  123,  123,  124],
+            [  47,   37,   42,   47,   48,   94,   96,  102],
+            [  29,   38,   51,   39,   33,   62,   64,   73],
+            [   8,   36,   45,   26,   16,   35,   48,   43],
+            [ -12,   23,   34,   25,   26,   25,   23,   15],
+            [  -5,  -27,   -3,   29,  -12,  -56,  -22,  -23],
+        ]),
+        king: PieceSquareTable([
+            [  -1,    0,    1,    0,    0,    0,    0,   -2],
+            [  -1,    6,   10,    3,    4,    8,    6,   -4],
+            [   0,   15,   19,    8,   12,   23,   18,   -5],
+            [   0,   15,   16,    7,    4,   17,    7,  -19],
+            [  -1,   11,   23,   -6,   13,    9,   23,  -33],
+            [ -12,  -11,   -4,  -26,   -8,  -14,   17,  -11],
+            [  25,    8,  -13,  -67,  -34,  -41,   22,   40],
+            [  -4,   50,   11,  -80,  -12,  -74,   37,   46],
+        ]),
+    },
+    endgame_piece_tables: PieceEvalSet {
+        pawn: PieceSquareTable([
+            [   0,    0,    0,    0,    0,    0,    0,    0],
+            [ 168,  163,  155,  129,  125,  127,  156,  156],
+            [ 149,  138,  117,   98,   86,   77,  105,  108],
+            [  75,   65,   54,   34,   29,   32,   47,   46],
+            [  52,   48,   27,   28,   24,   26,   33,   29],
+            [  44,   42,   30,   32,   36,   30,   34,   22],
+            [  55,   49,   44,   43,   47,   46,   39,   20],
+            [   0,    0,    0,    0,    0,    0,    0,    0],
+        ]),
+        knight: PieceSquareTable([
+            [ -76,  -34,  -12,  -21,  -17,  -15,  -21,  -43],
+            [ -61,  -28,  -18,   14,    5,  -26,  -31,  -52],
+            [ -39,   -7,   11,   14,   -2,    9,  -11,  -41],
+            [ -33,    2,   16,   32,   36,   24,   18,  -25],
+            [ -43,   -4,   17,   26,   22,   22,   -5,  -26],
+            [ -77,  -30,  -18,    2,   -2,  -20,  -31,  -76],
+            [ -56,  -36,  -41,  -30,  -26,  -48,  -42,  -71],
+            [ -77, -112,  -50,  -47,  -51,  -65,  -94,  -48],
+        ]),
+        bishop: PieceSquareTable([
+            [   5,    8,    8,   18,   14,    9,    1,    6],
+            [  -5,   15,   13,   19,   18,   12,   10,  -12],
+            [   0,   17,    9,   16,    9,   25,   10,    0],
+            [   1,   16,   13,   19,   32,   10,   28,    3],
+            [ -12,    9,   21,   22,   16,   18,    1,  -14],
+            [ -13,    1,   13,   13,   12,    3,  -12,  -13],
+            [ -28,  -19,  -14,   -5,   -8,  -25,  -15,  -61],
+            [ -30,  -20,  -42,  -20,  -16,  -31,  -21,  -24],
+        ]),
+        rook: PieceSquareTable([
+            [  78,   84,   88,   84,   82,   81,   76,   76],
+            [  79,   87,   85,   90,   84,   63,   67,   58],
+            [  78,   71,   78,   68,   52,   66,   43,   54],
+            [  70,   73,   75,   67,   70,   64,   52,   51],
+            [  54,   70,   73,   69,   67,   61,   50,   41],
+            [  37,   47,   49,   50,   50,   49,   28,   26],
+            [  41,   31,   37,   44,   42,   33,   25,   43],
+            [  34,   43,   53,   51,   49,   50,   38,   15],
+        ]),
+        queen: PieceSquareTable([
+            [  46,   62,   77,   75,   84,   84,   68,   54],
+            [  29,   72,   37,   81,   92,   73,   47,   36],
+            [   7,   15,   46,   46,   63,   70,   42,   17],
+            [ -22,   21,   18,   51,   83,   48,   37,    9],
+            [ -13,   15,   -7,   45,   51,   29,    7,    7],
+            [   6,  -12,   -3,   13,   26,   17,   -8,  -11],
+            [  15,   -7,  -31,   -7,  -13,  -28,  -19,    0],
+            [  20,   33,    7,  -39,   21,   45,   -3,    1],
+        ]),
+        king: PieceSquareTable([
+            [ -23,   -9,   -6,   -1,   -7,   -4,   -1,  -23],
+            [ -14,   30,   22,    9,   16,   23,   40,  -23],
+            [   9,   39,   37,   27,   28,   45,   47,    4],
+            [   5,   27,   37,   37,   37,   38,   33,    0],
+            [ -32,    7,   22,   34,   30,   22,    6,  -16],
+            [ -27,   -4,    3,   19,   14,    5,  -14,  -26],
+            [ -25,  -20,   -6,    3,   -4,    0,  -25,  -47],
+            [ -56,  -48,  -33,  -36,  -71,  -21,  -47,  -99],
+        ]),
+    },
+    pawn_structure: PawnStructureWeights {
+        doubled: TaperedValue { midgame: 10, endgame: 20 },
+        isolated: TaperedValue { midgame: 10, endgame: 15 },
+        backward: TaperedValue { midgame: 8, endgame: 10 }
+    },
+    drawish_scaling: DrawishScaling {
+        opposite_bishops: 32,
+        rook_and_pawn: 24,
+        wrong_bishop: 4,
+        no_pawns: 8,
+        queen_vs_pawn: 8
+    },
+    threats: ThreatWeights {
+        pawn_attacks_piece: TaperedValue { midgame: 40, endgame: 30 },
+        minor_attacks_major: TaperedValue { midgame: 30, endgame: 20 },
+        hanging: TaperedValue { midgame: 20, endgame: 15 }
+    },
+    halfmove_scaling: HalfmoveScaling {
+        start: 30
+    },
+    mop_up: MopUpWeights {
+        min_advantage: 400,
+        enemy_king_edge: 10,
+        king_proximity: 4,
+        bishop_corner: 20
+    },
+    passed_pawns: PassedPawnWeights {
+        rook_behind: TaperedValue { midgame: 10, endgame: 25 }
+    },
+    seventh_rank: SeventhRankWeights {
+        rook: TaperedValue { midgame: 20, endgame: 40 },
+        queen: TaperedValue { midgame: 10, endgame: 20 }
+    },
+    king_file: KingFileWeights {
+        open: TaperedValue { midgame: 30, endgame: 0 },
+        semi_open: TaperedValue { midgame: 15, endgame: 0 }
+    },
+    bad_bishop: BadBishopWeights {
+        same_color_pawn: TaperedValue { midgame: 2, endgame: 4 },
+        blocked_pawn: TaperedValue { midgame: 3, endgame: 3 }
+    },
+    light: false
+};
+
+///Squares attacked by the side's pawns.
+fn pawn_attacks(board: &Board, side: Color) -> BitBoard {
+    let pawns = (*board.pieces(Piece::Pawn) & *board.color_combined(side)).0;
+    const NOT_A_FILE: u64 = !0x0101_0101_0101_0101;
+    const NOT_H_FILE: u64 = !0x8080_8080_8080_8080;
+    BitBoard(match side {
+        Color::White => ((pawns & NOT_A_FILE) << 7) | ((pawns & NOT_H_FILE) << 9),
+        Color::Black => ((pawns & NOT_A_FILE) >> 9) | ((pawns & NOT_H_FILE) >> 7)
+    })
+}
+
+///Squares attacked by the given pieces of the side, which may not include pawns.
+fn piece_attacks(board: &Board, side: Color, pieces: BitBoard) -> BitBoard {
+    let blockers = *board.combined();
+    let mut attacks = EMPTY;
+    for square in pieces & *board.color_combined(side) {
+        attacks |= match board.piece_on(square) {
+            Some(Piece::Knight) => get_knight_moves(square),
+            Some(Piece::Bishop) => get_bishop_moves(square, blockers),
+            Some(Piece::Rook) => get_rook_moves(square, blockers),
+            Some(Piece::Queen) => get_bishop_moves(square, blockers) | get_rook_moves(square, blockers),
+            Some(Piece::King) => get_king_moves(square),
+            _ => EMPTY
+        };
+    }
+    attacks
+}
+
+///The ranks in front of a rank from the side's perspective.
+fn ranks_ahead(side: Color, rank: Rank) -> BitBoard {
+    ALL_RANKS
+        .iter()
+        .filter(|r| match side {
+            Color::White => r.to_index() > rank.to_index(),
+            Color::Black => r.to_index() < rank.to_index()
+        })
+        .fold(EMPTY, |ranks, &r| ranks | get_rank(r))
+}
+
+const DARK_SQUARES: BitBoard = BitBoard(0xAA55_AA55_AA55_AA55);
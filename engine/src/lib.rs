@@ -4,3 +4,10 @@ pub mod oracle;
 pub mod time;
 pub mod search;
 pub mod evaluator;
+pub mod analysis;
+pub mod bench;
+pub mod render;
+pub mod trend;
+pub mod config;
+
+pub use oracle::{probe_endgame, EndgameVerdict};
@@ -3,10 +3,10 @@ use std::iter::Peekable;
 
 use arrayvec::ArrayVec;
 use chess::*;
+use serde::{Serialize, Deserialize};
 
 use crate::evaluator::*;
-use crate::table::*;
-use crate::search::{HistoryTable, KillerTableEntry};
+use crate::search::{HistoryTable, PieceToHistory, KillerTableEntry, move_is_legal};
 
 struct MaxSelectionSorter<I>(Vec<I>);
 
@@ -52,7 +52,9 @@ impl Ord for SeeMove {
     }
 }
 
-fn static_exchange_evaluation(board: &Board, capture: ChessMove) -> Eval {
+///The material won or lost by the move if both sides keep recapturing on its square.
+///Also works for quiet moves, which only risk losing the moved piece.
+pub(crate) fn static_exchange_evaluation(board: &Board, capture: ChessMove) -> Eval {
     let color = board.side_to_move();
     let sq = capture.get_dest();
 
@@ -79,7 +81,14 @@ fn static_exchange_evaluation(board: &Board, capture: ChessMove) -> Eval {
     //...I don't really want to figure out the exact value.
     let mut gains = ArrayVec::<[Eval; 32]>::new();
     let mut side_to_move = color;
-    let mut square_piece_value = EVALUATOR.piece_value(board.piece_on(sq).unwrap());
+    //The square may be empty for quiet moves and en passant.
+    let is_en_passant = board.piece_on(capture.get_source()) == Some(Piece::Pawn) &&
+        capture.get_source().get_file() != sq.get_file();
+    let mut square_piece_value = match board.piece_on(sq) {
+        Some(piece) => EVALUATOR.piece_value(piece),
+        None if is_en_passant => EVALUATOR.piece_value(Piece::Pawn),
+        None => Eval::ZERO
+    };
     let mut attacker_square = capture.get_source();
     loop {
         //Reverse the roles if our piece is being attacked.
@@ -134,9 +143,67 @@ fn static_exchange_evaluation(board: &Board, capture: ChessMove) -> Eval {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum EvasionKind {
+    KingMove,
+    Block,
+    Capture
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct EvasionMove {
+    kind: EvasionKind,
+    see: SeeMove
+}
+
+impl PartialOrd for EvasionMove {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for EvasionMove {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.kind, &self.see).cmp(&(other.kind, &other.see))
+    }
+}
+
+///Orders check evasions: Captures of the checker first, then blocks,
+///then king moves including king captures, each ordered by SEE.
+fn sort_evasions(board: &Board, moves: impl Iterator<Item=ChessMove>) -> MaxSelectionSorter<EvasionMove> {
+    let checkers = *board.checkers();
+    let evasions = moves.map(|mv| {
+        let piece = board.piece_on(mv.get_source());
+        let is_en_passant = piece == Some(Piece::Pawn) &&
+            mv.get_source().get_file() != mv.get_dest().get_file() &&
+            board.piece_on(mv.get_dest()).is_none();
+        let captured = if is_en_passant {
+            mv.get_dest().ubackward(board.side_to_move())
+        } else {
+            mv.get_dest()
+        };
+        let kind = if piece == Some(Piece::King) {
+            EvasionKind::KingMove
+        } else if checkers & BitBoard::from_square(captured) != EMPTY {
+            EvasionKind::Capture
+        } else {
+            EvasionKind::Block
+        };
+        EvasionMove {
+            kind,
+            see: SeeMove {
+                value: static_exchange_evaluation(board, mv),
+                mv
+            }
+        }
+    }).collect();
+    MaxSelectionSorter(evasions)
+}
+
 pub struct SortedMoveGenerator {
     board: Board,
     pv_move: Option<ChessMove>,
+    evasions: Option<MaxSelectionSorter<EvasionMove>>,
     captures: Option<Peekable<MaxSelectionSorter<SeeMove>>>,
     killers: KillerTableEntry,
     quiets: Option<Vec<ChessMove>>,
@@ -144,16 +211,17 @@ pub struct SortedMoveGenerator {
 }
 
 impl SortedMoveGenerator {
+    ///`pv_move` must be legal, so moves from the transposition table should be checked with `move_is_legal` first.
     pub fn new(
-        table: &TranspositionTable,
+        pv_move: Option<ChessMove>,
         killers: KillerTableEntry,
         board: Board,
         moves: MoveGen
     ) -> Self {
-        let pv_move = table.get(&board).map(|entry| entry.best_move);
         Self {
             board,
             pv_move,
+            evasions: None,
             captures: None,
             killers,
             quiets: None,
@@ -161,13 +229,36 @@ impl SortedMoveGenerator {
         }
     }
 
-    pub fn next(&mut self, history_table: &HistoryTable) -> Option<ChessMove> {
+    ///Only emit moves that `allowed` accepts.
+    pub fn retain(&mut self, mut allowed: impl FnMut(ChessMove) -> bool) {
+        self.pv_move = self.pv_move.filter(|&mv| allowed(mv));
+        self.killers.retain(|&mv| allowed(mv));
+        for mv in MoveGen::new_legal(&self.board) {
+            if !allowed(mv) {
+                self.moves.remove_move(mv);
+            }
+        }
+    }
+
+    ///Quiets are ordered by the sum of their history and continuation history scores.
+    pub fn next(
+        &mut self,
+        history_table: &HistoryTable,
+        continuation_history: Option<&PieceToHistory>
+    ) -> Option<ChessMove> {
         if let Some(mv) = self.pv_move.take() {
             self.moves.remove_move(mv);
             self.killers.retain(|&m| m != mv);
             return Some(mv);
         }
 
+        if *self.board.checkers() != EMPTY {
+            if self.evasions.is_none() {
+                self.evasions = Some(sort_evasions(&self.board, &mut self.moves));
+            }
+            return self.evasions.as_mut().unwrap().next().map(|mv| mv.see.mv);
+        }
+
         if self.captures.is_none() {
             let mut see_moves = Vec::with_capacity(40);
             self.moves.set_iterator_mask(*self.board.combined());
@@ -199,13 +290,12 @@ impl SortedMoveGenerator {
         }
 
         while let Some(mv) = self.killers.pop_front() {
-            let mut moves = MoveGen::new_legal(&self.board);
-            moves.set_iterator_mask(BitBoard::from_square(mv.get_dest()));
-            for m in moves {
-                if m.get_source() == mv.get_source() {
-                    self.moves.remove_move(mv);
-                    return Some(mv);
-                }
+            //Killers come from sibling positions, so they may be illegal or hanging here.
+            //Hanging killers are left to be ordered with the other quiets.
+            if move_is_legal(&self.board, mv) &&
+                static_exchange_evaluation(&self.board, mv) >= Eval::ZERO {
+                self.moves.remove_move(mv);
+                return Some(mv);
             }
         }
 
@@ -220,10 +310,15 @@ impl SortedMoveGenerator {
                 .iter()
                 .enumerate()
                 .max_by_key(|(_, mv)| {
-                    history_table
+                    let piece = board.piece_on(mv.get_source()).unwrap().to_index();
+                    let history = history_table
                         [board.side_to_move().to_index()]
-                        [board.piece_on(mv.get_source()).unwrap().to_index()]
-                        [mv.get_dest().to_index()]
+                        [piece]
+                        [mv.get_dest().to_index()];
+                    let continuation = continuation_history
+                        .map(|table| table[piece][mv.get_dest().to_index()])
+                        .unwrap_or_default();
+                    history.saturating_add(continuation)
                 })
                 .unwrap()
                 .0;
@@ -235,20 +330,75 @@ impl SortedMoveGenerator {
     }
 }
 
-pub fn quiescence_move_generator(board: &Board, mut moves: MoveGen) -> impl Iterator<Item=ChessMove> {
+enum QuiescenceMoves {
+    Captures(MaxSelectionSorter<SeeMove>, std::vec::IntoIter<ChessMove>),
+    Evasions(MaxSelectionSorter<EvasionMove>)
+}
+
+impl Iterator for QuiescenceMoves {
+    type Item = ChessMove;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Captures(captures, checks) => captures
+                .next()
+                .map(|mv| mv.mv)
+                .or_else(|| checks.next()),
+            Self::Evasions(evasions) => evasions.next().map(|mv| mv.see.mv)
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuiescenceChecks {
+    None,
+    All,
+    ///Only checks with a non-negative static exchange evaluation
+    NonLosing
+}
+
+///Generates captures ordered by SEE followed by quiet checks, or all evasions if in check.
+///Losing captures are skipped if `prune_losing` is set.
+pub fn quiescence_move_generator(
+    board: &Board,
+    mut moves: MoveGen,
+    prune_losing: bool,
+    checks: QuiescenceChecks
+) -> impl Iterator<Item=ChessMove> {
+    if *board.checkers() != EMPTY {
+        return QuiescenceMoves::Evasions(sort_evasions(board, moves));
+    }
+
     //Chess branching factor is ~35
     let mut see_moves = Vec::with_capacity(40);
     //TODO excludes en-passant, does this matter?
     moves.set_iterator_mask(*board.combined());
-    for mv in moves {
+    for mv in &mut moves {
         let value = static_exchange_evaluation(
             board,
             mv
         );
+        if prune_losing && value < Eval::ZERO {
+            continue;
+        }
         see_moves.push(SeeMove {
             value,
             mv
         });
     }
-    MaxSelectionSorter(see_moves).map(|mv| mv.mv)
+
+    let mut quiet_checks = Vec::new();
+    if checks != QuiescenceChecks::None {
+        moves.set_iterator_mask(!EMPTY);
+        for mv in moves {
+            if *board.make_move_new(mv).checkers() == EMPTY {
+                continue;
+            }
+            if checks == QuiescenceChecks::NonLosing && static_exchange_evaluation(board, mv) < Eval::ZERO {
+                continue;
+            }
+            quiet_checks.push(mv);
+        }
+    }
+    QuiescenceMoves::Captures(MaxSelectionSorter(see_moves), quiet_checks.into_iter())
 }
@@ -2,6 +2,9 @@ use chess::*;
 
 use crate::evaluator::*;
 
+const DARK_SQUARES: BitBoard = BitBoard(0xAA55_AA55_AA55_AA55);
+
+///Exact results for positions with little material. Expects a game that isn't over.
 pub fn oracle(board: &Board) -> Option<Eval> {
     let all_pieces = *board.combined();
     let white_pieces = *board.color_combined(Color::White);
@@ -9,6 +12,13 @@ pub fn oracle(board: &Board) -> Option<Eval> {
     let knights = *board.pieces(Piece::Knight);
     let kings = *board.pieces(Piece::King);
 
+    //Only bishops that all stand on squares of one color. Nothing can ever cover
+    //the king's escape squares of the other color, so no checkmate is possible
+    //however many bishops there are.
+    if all_pieces & !kings == bishops && (bishops & DARK_SQUARES == EMPTY || bishops & !DARK_SQUARES == EMPTY) {
+        return Some(Eval::DRAW);
+    }
+
     match all_pieces.popcnt() {
         0 | 1 => unreachable!(),
         2 => Some(Eval::DRAW),
@@ -21,43 +31,73 @@ pub fn oracle(board: &Board) -> Option<Eval> {
             }
         }
         4 => {
-            const fn dark_squares() -> BitBoard {
-                let mut board: u64 = 1;
-                while board.count_ones() < 32 {
-                    board |= board << 2;
-                }
-                BitBoard(board)
-            }
             const CORNERS: BitBoard = BitBoard(
-                (1 << 1) | (1 << 7) | (1 << 56) | (1 << 63)
+                (1 << 0) | (1 << 7) | (1 << 56) | (1 << 63)
             );
             let one_piece_each = white_pieces.popcnt() == 2;
 
-            //KNvKN KNNvk. Always a draw except for a few positions that are mate in one.
-            //All of those positions have a king on an edge and are incredibly rare,
-            //so we just do a quick check for edge kings before returning a draw.
-            if knights.popcnt() == 2 && (kings & EDGES) == EMPTY {
-                return Some(Eval::DRAW);
-            }
-            if bishops.popcnt() == 2 {
-                if (bishops & dark_squares()).popcnt() != 1 {
-                    //Both bishops are on the same color square
-                    return Some(Eval::DRAW);
+            if knights.popcnt() == 2 {
+                if !one_piece_each {
+                    return two_knights(board);
                 }
-                if one_piece_each && (kings & CORNERS) == EMPTY {
-                    //Opposite color bishops. Check the corners
-                    //since there's technically one checkmate.
+                //KNvKN. Always a draw except for a few positions that are mate in one.
+                //All of those positions have a king on an edge and are incredibly rare,
+                //so we just do a quick check for edge kings before returning a draw.
+                if (kings & EDGES) == EMPTY {
                     return Some(Eval::DRAW);
                 }
             }
-            if knights.popcnt() == 1 && bishops.popcnt() == 1 {
-                if one_piece_each && (kings & CORNERS) == EMPTY {
-                    //Check the corners since there's technically one checkmate.
-                    return Some(Eval::DRAW);
-                }
+            //Same colored bishops are already handled, so these are opposite colored.
+            //Check the corners since there's technically one checkmate.
+            if bishops.popcnt() == 2 && one_piece_each && (kings & CORNERS) == EMPTY {
+                return Some(Eval::DRAW);
+            }
+            //Check the corners since there's technically one checkmate.
+            if knights.popcnt() == 1 && bishops.popcnt() == 1 && one_piece_each && (kings & CORNERS) == EMPTY {
+                return Some(Eval::DRAW);
             }
             None
         }
         _ => None
     }
 }
+
+///KNNvK. Two knights can't force mate, so it's a draw unless mate is already
+///at hand: the knights can mate in one, or the lone king has only one move left.
+fn two_knights(board: &Board) -> Option<Eval> {
+    let knights = *board.pieces(Piece::Knight);
+    let mut moves = MoveGen::new_legal(board);
+    if knights & *board.color_combined(board.side_to_move()) != EMPTY {
+        let mate_in_one = moves.any(|mv| board.make_move_new(mv).status() == BoardStatus::Checkmate);
+        if mate_in_one {
+            return None;
+        }
+    } else if moves.len() <= 1 {
+        return None;
+    }
+    Some(Eval::DRAW)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EndgameVerdict {
+    Win,
+    Draw,
+    Loss
+}
+
+///The known result for the side to move, without running a search.
+///Finished games are reported too. Only the built in oracle is consulted,
+///as there are no bitbases or tablebases yet.
+pub fn probe_endgame(board: &Board) -> Option<EndgameVerdict> {
+    match board.status() {
+        BoardStatus::Checkmate => return Some(EndgameVerdict::Loss),
+        BoardStatus::Stalemate => return Some(EndgameVerdict::Draw),
+        BoardStatus::Ongoing => {}
+    }
+    match oracle(board)?.kind() {
+        EvalKind::MateIn(_) => Some(EndgameVerdict::Win),
+        EvalKind::MatedIn(_) => Some(EndgameVerdict::Loss),
+        EvalKind::Centipawn(0) => Some(EndgameVerdict::Draw),
+        EvalKind::Centipawn(_) => None
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt::Write;
+
+use chess::*;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RenderStyle {
+    Ascii,
+    Unicode
+}
+
+fn piece_char(piece: Piece, color: Color, style: RenderStyle) -> char {
+    match style {
+        RenderStyle::Ascii => piece.to_string(color).chars().next().unwrap(),
+        RenderStyle::Unicode => {
+            let glyphs = match color {
+                Color::White => ['♙', '♘', '♗', '♖', '♕', '♔'],
+                Color::Black => ['♟', '♞', '♝', '♜', '♛', '♚']
+            };
+            glyphs[piece.to_index()]
+        }
+    }
+}
+
+///Renders the board from white's perspective with file and rank labels.
+///The source and destination of `last_move` are highlighted with brackets.
+pub fn board(board: &Board, style: RenderStyle, last_move: Option<ChessMove>) -> String {
+    let highlighted = last_move
+        .map(|mv| BitBoard::from_square(mv.get_source()) | BitBoard::from_square(mv.get_dest()))
+        .unwrap_or(EMPTY);
+    let mut out = String::new();
+    for &rank in ALL_RANKS.iter().rev() {
+        write!(out, "{} ", rank.to_index() + 1).unwrap();
+        for &file in &ALL_FILES {
+            let square = Square::make_square(rank, file);
+            let piece = match (board.piece_on(square), board.color_on(square)) {
+                (Some(piece), Some(color)) => piece_char(piece, color, style),
+                _ => '.'
+            };
+            if highlighted & BitBoard::from_square(square) != EMPTY {
+                write!(out, "[{}]", piece).unwrap();
+            } else {
+                write!(out, " {} ", piece).unwrap();
+            }
+        }
+        out.push('\n');
+    }
+    out.push_str("   a  b  c  d  e  f  g  h\n");
+    out
+}
+
+///The board's FEN. Move counters aren't tracked by `Board`, so they're always `0 1`.
+pub fn fen(board: &Board) -> String {
+    board.to_string()
+}
+
+///The board's EPD, the first four fields of its FEN.
+pub fn epd(board: &Board) -> String {
+    board.to_string()
+        .split(' ')
+        .take(4)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+///Renders a line of moves played from `board` as numbered arrows, like `1. e2->e4 e7->e5`.
+///Numbering starts from 1 since `Board` doesn't track the move number.
+pub fn principal_variation(board: &Board, line: &[ChessMove], style: RenderStyle) -> String {
+    let arrow = match style {
+        RenderStyle::Ascii => "->",
+        RenderStyle::Unicode => "→"
+    };
+    let mut out = String::new();
+    let mut side_to_move = board.side_to_move();
+    let mut move_number = 1;
+    for (index, &mv) in line.iter().enumerate() {
+        if index > 0 {
+            out.push(' ');
+        }
+        match side_to_move {
+            Color::White => write!(out, "{}. ", move_number).unwrap(),
+            Color::Black if index == 0 => write!(out, "{}... ", move_number).unwrap(),
+            Color::Black => {}
+        }
+        write!(out, "{}{}{}", mv.get_source(), arrow, mv.get_dest()).unwrap();
+        if let Some(promotion) = mv.get_promotion() {
+            out.push_str(&promotion.to_string(Color::Black));
+        }
+        if side_to_move == Color::Black {
+            move_number += 1;
+        }
+        side_to_move = !side_to_move;
+    }
+    out
+}
@@ -0,0 +1,146 @@
+use std::convert::TryFrom;
+use std::sync::OnceLock;
+
+use chess::*;
+
+//Upcoming repetition detection, based on Marcel van Kervinck's cuckoo tables as used by Stockfish.
+//Every reversible move of a non-pawn piece changes the hash by a fixed key,
+//so a position is one move away from an earlier one if their hashes differ by one of these keys.
+
+const TABLE_SIZE: usize = 8192;
+
+fn h1(key: u64) -> usize {
+    key as usize & (TABLE_SIZE - 1)
+}
+
+fn h2(key: u64) -> usize {
+    (key >> 16) as usize & (TABLE_SIZE - 1)
+}
+
+struct CuckooTable {
+    keys: Box<[u64; TABLE_SIZE]>,
+    moves: Box<[Option<(Square, Square)>; TABLE_SIZE]>
+}
+
+///The chess crate doesn't expose its Zobrist keys, so the key of a move is recovered
+///from the hashes of two boards that only differ by where the piece stands.
+fn move_key(piece: Piece, color: Color, source: Square, dest: Square) -> u64 {
+    let position = |king: Square, enemy_king: Square, square: Square| {
+        let mut builder = BoardBuilder::new();
+        builder.piece(enemy_king, Piece::King, !color);
+        if piece != Piece::King {
+            builder.piece(king, Piece::King, color);
+        }
+        builder.piece(square, piece, color);
+        builder.side_to_move(!color);
+        Board::try_from(builder).ok()
+    };
+    //Any placement of the kings that's valid with the piece on either square works.
+    ALL_SQUARES
+        .iter()
+        .flat_map(|&king| ALL_SQUARES.iter().map(move |&enemy_king| (king, enemy_king)))
+        .filter(|&(king, enemy_king)| {
+            let occupied = BitBoard::from_square(source) | BitBoard::from_square(dest);
+            let kings = BitBoard::from_square(king) | BitBoard::from_square(enemy_king);
+            king != enemy_king && occupied & kings == EMPTY
+        })
+        .find_map(|(king, enemy_king)| {
+            let before = position(king, enemy_king, source)?;
+            let after = position(king, enemy_king, dest)?;
+            Some(before.get_hash() ^ after.get_hash())
+        })
+        .unwrap()
+}
+
+fn side_key() -> u64 {
+    let board = Board::default();
+    board.get_hash() ^ board.null_move().unwrap().get_hash()
+}
+
+fn build() -> CuckooTable {
+    let mut table = CuckooTable {
+        keys: Box::new([0; TABLE_SIZE]),
+        moves: Box::new([None; TABLE_SIZE])
+    };
+    let side_key = side_key();
+    for &color in &ALL_COLORS {
+        for &piece in &[Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King] {
+            for &source in &ALL_SQUARES {
+                let attacks = match piece {
+                    Piece::Knight => get_knight_moves(source),
+                    Piece::Bishop => get_bishop_rays(source),
+                    Piece::Rook => get_rook_rays(source),
+                    Piece::Queen => get_bishop_rays(source) | get_rook_rays(source),
+                    _ => get_king_moves(source)
+                };
+                for dest in attacks {
+                    if dest.to_index() <= source.to_index() {
+                        continue;
+                    }
+                    let mut key = move_key(piece, color, source, dest) ^ side_key;
+                    let mut mv = Some((source, dest));
+                    //Displace entries until one lands in an empty slot
+                    let mut index = h1(key);
+                    loop {
+                        std::mem::swap(&mut table.keys[index], &mut key);
+                        std::mem::swap(&mut table.moves[index], &mut mv);
+                        if mv.is_none() {
+                            break;
+                        }
+                        index = if index == h1(key) { h2(key) } else { h1(key) };
+                    }
+                }
+            }
+        }
+    }
+    table
+}
+
+fn table() -> &'static CuckooTable {
+    static TABLE: OnceLock<CuckooTable> = OnceLock::new();
+    TABLE.get_or_init(build)
+}
+
+///The reversible move between two positions whose hashes XOR to `key`, if there is one.
+pub fn cuckoo_move(key: u64) -> Option<(Square, Square)> {
+    let table = table();
+    [h1(key), h2(key)]
+        .iter()
+        .find(|&&index| table.keys[index] == key)
+        .and_then(|&index| table.moves[index])
+}
+
+///Can the side to move reach a position from `history` with one reversible move?
+///`history` holds the hashes of earlier positions, most recent first, starting with the board itself.
+///`reversible_plies` bounds how far back a position could be reached from,
+///and positions closer than `line_plies` are part of the current search line.
+///The repetition would be reached in the search line, so like in `draw_by_move_rule`
+///a single repetition is a draw, even of a position from before the line.
+pub fn upcoming_repetition(
+    board: &Board,
+    history: impl Iterator<Item=u64> + Clone,
+    reversible_plies: u8,
+    line_plies: u8
+) -> bool {
+    if reversible_plies < 3 {
+        return false;
+    }
+    let hash = board.get_hash();
+    let candidates = history.clone().enumerate().take(reversible_plies as usize + 1).skip(3).step_by(2);
+    for (plies, earlier) in candidates {
+        if let Some((source, dest)) = cuckoo_move(hash ^ earlier) {
+            if between(source, dest) & board.combined() != EMPTY {
+                continue;
+            }
+            if plies < line_plies as usize {
+                return true;
+            }
+            //Earlier positions can be from before the root, so the move must actually be ours.
+            let square = if board.piece_on(source).is_some() { source } else { dest };
+            if board.color_on(square) == Some(board.side_to_move()) {
+                return true;
+            }
+        }
+    }
+    false
+}
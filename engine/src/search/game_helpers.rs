@@ -1,5 +1,7 @@
 use chess::*;
 
+use crate::evaluator::*;
+
 pub fn move_resets_fifty_move_rule(mv: ChessMove, board: &Board) -> bool {
     // The only capturing move that doesn't move to the captured piece's square
     // is en passant, which is a pawn move and zeroes anyway
@@ -13,6 +15,142 @@ pub fn move_is_quiet(board: &Board, child_board: &Board) -> bool {
     child_board.pieces(Piece::Pawn).popcnt() == board.pieces(Piece::Pawn).popcnt()
 }
 
+///A pawn push to the 6th or 7th rank with no enemy pawns
+///able to stop it on its own or adjacent files.
+pub fn is_passed_pawn_push(board: &Board, mv: ChessMove) -> bool {
+    if board.piece_on(mv.get_source()) != Some(Piece::Pawn) || mv.get_promotion().is_some() {
+        return false;
+    }
+    let side = board.side_to_move();
+    let dest = mv.get_dest();
+    let rank = dest.get_rank().to_index();
+    let relative_rank = match side {
+        Color::White => rank,
+        Color::Black => 7 - rank
+    };
+    if relative_rank < 5 {
+        return false;
+    }
+    let ranks_ahead = ALL_RANKS
+        .iter()
+        .filter(|r| match side {
+            Color::White => r.to_index() > rank,
+            Color::Black => r.to_index() < rank
+        })
+        .fold(EMPTY, |ranks, &r| ranks | get_rank(r));
+    let files = get_file(dest.get_file()) | get_adjacent_files(dest.get_file());
+    let enemy_pawns = board.pieces(Piece::Pawn) & board.color_combined(!side);
+    enemy_pawns & files & ranks_ahead == EMPTY
+}
+
+///A cheap check that the move is possible for the piece on the source square,
+///ignoring pins and checks. Castling and en passant are left to `move_is_legal`.
+pub fn move_is_pseudo_legal(board: &Board, mv: ChessMove) -> bool {
+    let (source, dest) = (mv.get_source(), mv.get_dest());
+    let us = board.side_to_move();
+    if board.color_on(source) != Some(us) || board.color_on(dest) == Some(us) {
+        return false;
+    }
+    let piece = board.piece_on(source).unwrap();
+    let promotion_rank = dest.get_rank() == us.to_their_backrank();
+    if mv.get_promotion().is_some() != (piece == Piece::Pawn && promotion_rank) {
+        return false;
+    }
+    let blockers = *board.combined();
+    let dest_bitboard = BitBoard::from_square(dest);
+    let targets = match piece {
+        Piece::Pawn => {
+            let captures = get_pawn_attacks(source, us, *board.color_combined(!us));
+            let en_passant = board.en_passant()
+                .map(|square| square.ubackward(!us))
+                .filter(|&square| get_pawn_attacks(source, us, !EMPTY) & BitBoard::from_square(square) != EMPTY);
+            return (get_pawn_quiets(source, us, blockers) | captures) & dest_bitboard != EMPTY ||
+                en_passant == Some(dest);
+        }
+        Piece::Knight => get_knight_moves(source),
+        Piece::Bishop => get_bishop_moves(source, blockers),
+        Piece::Rook => get_rook_moves(source, blockers),
+        Piece::Queen => get_bishop_moves(source, blockers) | get_rook_moves(source, blockers),
+        Piece::King => {
+            //Castling moves two files
+            let castles = source.get_file().to_index().abs_diff(dest.get_file().to_index()) == 2;
+            return castles || get_king_moves(source) & dest_bitboard != EMPTY;
+        }
+    };
+    targets & dest_bitboard != EMPTY
+}
+
+///Checks a move that came from elsewhere, like the transposition table or killers,
+///without generating the legal moves. Castling and en passant are rare enough to leave to the move generator.
+pub fn move_is_legal(board: &Board, mv: ChessMove) -> bool {
+    if !move_is_pseudo_legal(board, mv) {
+        return false;
+    }
+    let (source, dest) = (mv.get_source(), mv.get_dest());
+    let us = board.side_to_move();
+    let piece = board.piece_on(source).unwrap();
+    let castles = piece == Piece::King && source.get_file().to_index().abs_diff(dest.get_file().to_index()) == 2;
+    let en_passant = piece == Piece::Pawn && board.en_passant().map(|square| square.ubackward(!us)) == Some(dest);
+    if castles || en_passant {
+        let mut moves = MoveGen::new_legal(board);
+        moves.set_iterator_mask(BitBoard::from_square(dest));
+        return moves.any(|m| m == mv);
+    }
+
+    let king = board.king_square(us);
+    if piece == Piece::King {
+        //The king can't hide behind itself from sliders
+        let blockers = *board.combined() ^ BitBoard::from_square(king);
+        return !square_attacked(board, dest, !us, blockers);
+    }
+    let checkers = *board.checkers();
+    let evasion_targets = match checkers.popcnt() {
+        0 => !EMPTY,
+        1 => between(king, checkers.to_square()) | checkers,
+        _ => return false
+    };
+    if evasion_targets & BitBoard::from_square(dest) == EMPTY {
+        return false;
+    }
+    //Pinned pieces may only move along the pin
+    *board.pinned() & BitBoard::from_square(source) == EMPTY || line(king, source) & BitBoard::from_square(dest) != EMPTY
+}
+
+///Is the square attacked by `attacker`'s pieces, with `blockers` blocking sliders?
+fn square_attacked(board: &Board, square: Square, attacker: Color, blockers: BitBoard) -> bool {
+    let their = |piece| *board.pieces(piece) & board.color_combined(attacker);
+    let queens = their(Piece::Queen);
+    get_rook_moves(square, blockers) & (their(Piece::Rook) | queens) != EMPTY ||
+        get_bishop_moves(square, blockers) & (their(Piece::Bishop) | queens) != EMPTY ||
+        get_knight_moves(square) & their(Piece::Knight) != EMPTY ||
+        get_pawn_attacks(square, !attacker, their(Piece::Pawn)) != EMPTY ||
+        get_king_moves(square) & their(Piece::King) != EMPTY
+}
+
+///Plays the moves from the initial position.
+///Also returns the hashes of the positions since the last irreversible move, up to and including the final one.
+pub fn play_moves(init_pos: &Board, moves: impl IntoIterator<Item=ChessMove>) -> (Board, Vec<u64>) {
+    let mut history = Vec::with_capacity(100);
+    let mut board = *init_pos;
+    history.push(board.get_hash());
+    for mv in moves {
+        if move_resets_fifty_move_rule(mv, &board) {
+            history.clear();
+        }
+        board = board.make_move_new(mv);
+        history.push(board.get_hash());
+    }
+    (board, history)
+}
+
+///Non-pawn material of both sides in centipawns
+pub fn non_pawn_material(board: &Board, evaluator: &StandardEvaluator) -> i32 {
+    [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+        .iter()
+        .map(|&piece| board.pieces(piece).popcnt() as i32 * *evaluator.piece_values.get(piece) as i32)
+        .sum()
+}
+
 pub fn board_status(board: &Board, moves: &MoveGen) -> BoardStatus {
     if moves.len() > 0 {
         BoardStatus::Ongoing
@@ -23,26 +161,34 @@ pub fn board_status(board: &Board, moves: &MoveGen) -> BoardStatus {
     }
 }
 
-pub fn draw_by_move_rule(board: &Board, game_history: &[u64], halfmove_clock: u8) -> bool {
+///`history` holds the hashes of earlier positions, most recent first, starting with the board itself.
+///The first `line_plies` of them are positions in the current search line.
+pub fn draw_by_move_rule(
+    board: &Board,
+    history: impl Iterator<Item=u64>,
+    halfmove_clock: u8,
+    line_plies: u8
+) -> bool {
     //Fifty move rule
     if halfmove_clock >= 100 {
         return true;
     }
 
-    //Threefold repetition
+    //Repetitions
     //Skip the first move (2 plies) and ensure at least one other move to compare it to (2 plies)
     if halfmove_clock >= 4 {
-        //Any repetition means a loop where the best move involves repeating moves, so
-        //the first repetition is immediately a draw. No point playing out three repetitions.
-
-        let threefold = game_history
-            .iter()
-            .rev()
-            .take(halfmove_clock as usize)
+        //A repetition reached in the search line means a loop where the best move involves repeating moves,
+        //so the first repetition is immediately a draw, even of a position played before the root.
+        //No point playing out three repetitions.
+        //Only the root itself was actually reached in the game, so it needs a real threefold.
+        let required = if line_plies > 0 { 1 } else { 2 };
+        let repetitions = history
+            .take(halfmove_clock as usize + 1) // The board and every position since the last irreversible move
             .step_by(2) // Every second ply so it's our turn
             .skip(1) // Skip our board
-            .any(|&hash| hash == board.get_hash());
-        if threefold {
+            .filter(|&hash| hash == board.get_hash())
+            .count();
+        if repetitions >= required {
             return true;
         }
     }
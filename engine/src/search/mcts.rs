@@ -0,0 +1,305 @@
+use std::time::Instant;
+
+use chess::*;
+use serde::{Serialize, Deserialize};
+
+use super::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MctsOptions {
+    ///The exploration constant in the PUCT formula
+    pub exploration: f32,
+    ///How many centipawns a move must be better by to get e times the prior
+    pub policy_temperature: f32,
+    ///Centipawns per unit of logit when mapping evaluations to expected scores
+    pub value_scale: f32,
+    ///The search stops once the tree has this many nodes
+    pub max_tree_nodes: usize,
+    ///A result is reported every this many playouts
+    pub report_interval: u64,
+    ///The evaluator used for priors and leaf values
+    pub evaluator: StandardEvaluator
+}
+
+impl Default for MctsOptions {
+    fn default() -> Self {
+        Self {
+            exploration: 1.5,
+            policy_temperature: 100.0,
+            value_scale: 400.0,
+            max_tree_nodes: 1_000_000,
+            report_interval: 4096,
+            evaluator: StandardEvaluator::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    ///The move leading to this node. Only the root has none.
+    mv: Option<ChessMove>,
+    prior: f32,
+    visits: u32,
+    ///Sum of expected scores for the side that played `mv`
+    total: f32,
+    first_child: u32,
+    child_count: u8,
+    expanded: bool,
+    ///The expected score for the side to move if the game is over here
+    terminal: Option<f32>
+}
+
+impl Node {
+    fn new(mv: Option<ChessMove>, prior: f32) -> Self {
+        Self {
+            mv,
+            prior,
+            visits: 0,
+            total: 0.0,
+            first_child: 0,
+            child_count: 0,
+            expanded: false,
+            terminal: None
+        }
+    }
+
+    ///The average expected score for the side that played `mv`
+    fn mean(&self) -> f32 {
+        self.total / self.visits.max(1) as f32
+    }
+
+    fn children(&self) -> std::ops::Range<usize> {
+        self.first_child as usize..self.first_child as usize + self.child_count as usize
+    }
+}
+
+///Mate scores count as a large but finite amount of centipawns.
+fn centipawns(value: Eval) -> f32 {
+    match value.kind() {
+        EvalKind::Centipawn(cp) => cp as f32,
+        EvalKind::MateIn(_) => 30_000.0,
+        EvalKind::MatedIn(_) => -30_000.0
+    }
+}
+
+///Experimental Monte Carlo tree search with PUCT selection.
+///Priors come from a softmax over the static evaluations of the children,
+///and leaves are scored by the best static evaluation of their children.
+///A ply of depth in `SearchLimits` is a ply of tree depth, and every playout counts as a node.
+#[derive(Debug, Clone, Default)]
+pub struct MctsSearcher {
+    pub options: MctsOptions
+}
+
+impl MctsSearcher {
+    fn expected_score(&self, value: Eval) -> f32 {
+        match value.kind() {
+            EvalKind::Centipawn(cp) => 1.0 / (1.0 + (-cp as f32 / self.options.value_scale).exp()),
+            EvalKind::MateIn(_) => 1.0,
+            EvalKind::MatedIn(_) => 0.0
+        }
+    }
+
+    fn centipawns(&self, score: f32) -> Eval {
+        let score = score.clamp(0.001, 0.999);
+        Eval::cp((self.options.value_scale * (score / (1.0 - score)).ln()) as i16)
+    }
+
+    fn select_child(&self, tree: &[Node], node: usize) -> usize {
+        let parent = &tree[node];
+        let explore = self.options.exploration * (parent.visits as f32).sqrt();
+        //Unvisited children are assumed to be as good as the parent
+        let first_play = 1.0 - parent.mean();
+        parent.children()
+            .map(|child| {
+                let node = &tree[child];
+                let value = if node.visits > 0 { node.mean() } else { first_play };
+                (child, value + explore * node.prior / (1 + node.visits) as f32)
+            })
+            .fold((parent.first_child as usize, f32::MIN), |best, next| {
+                if next.1 > best.1 { next } else { best }
+            })
+            .0
+    }
+
+    ///Expands the node, returning the expected score for the side to move.
+    fn expand(&self, tree: &mut Vec<Node>, node: usize, board: &Board, moves: MoveGen) -> f32 {
+        let children = moves
+            .map(|mv| (mv, -self.options.evaluator.evaluate(&board.make_move_new(mv))))
+            .collect::<Vec<_>>();
+        let best = children.iter().map(|&(_, value)| value).max().unwrap();
+        let logits = children
+            .iter()
+            .map(|&(_, value)| {
+                let difference = centipawns(value) - centipawns(best);
+                (difference / self.options.policy_temperature).exp()
+            })
+            .collect::<Vec<_>>();
+        let sum = logits.iter().sum::<f32>();
+
+        tree[node].first_child = tree.len() as u32;
+        tree[node].child_count = children.len() as u8;
+        tree[node].expanded = true;
+        for (&(mv, _), logit) in children.iter().zip(logits) {
+            tree.push(Node::new(Some(mv), logit / sum));
+        }
+        self.expected_score(best)
+    }
+
+    ///The most visited children from the root, breaking ties by prior.
+    ///The root always has a move, even if none of its children were visited yet.
+    fn principal_variation(&self, tree: &[Node]) -> Vec<usize> {
+        let mut line = Vec::new();
+        let mut node = 0;
+        let best_child = |node: usize| tree[node].children().max_by(|&a, &b| {
+            let (a, b) = (&tree[a], &tree[b]);
+            a.visits.cmp(&b.visits).then(a.prior.total_cmp(&b.prior))
+        });
+        while let Some(child) = best_child(node) {
+            if tree[child].visits == 0 && node != 0 {
+                break;
+            }
+            line.push(child);
+            node = child;
+        }
+        line
+    }
+}
+
+impl Searcher for MctsSearcher {
+    fn search<H: LunaticHandler>(
+        &mut self,
+        mut handler: H,
+        init_pos: &Board,
+        moves: Vec<ChessMove>,
+        limits: SearchLimits
+    ) {
+        let search_begin = Instant::now();
+        let (root, game_history) = play_moves(init_pos, moves);
+        let root_halfmove_clock = game_history.len() as u8 - 1;
+        let root_moves = MoveGen::new_legal(&root).len();
+        if root_moves == 0 {
+            return;
+        }
+        //With only one legal move there's nothing to think about.
+        let only_move = root_moves == 1;
+
+        let mut tree = vec![Node::new(None, 1.0)];
+        let mut path = Vec::new();
+        let mut line_history = Vec::new();
+        let mut playouts = 0;
+        let mut sel_depth = 0;
+        loop {
+            let mut board = root;
+            let mut halfmove_clock = root_halfmove_clock;
+            let mut node = 0;
+            path.clear();
+            path.push(node);
+            line_history.clear();
+            while tree[node].expanded && tree[node].terminal.is_none() {
+                node = self.select_child(&tree, node);
+                let mv = tree[node].mv.unwrap();
+                halfmove_clock = if move_resets_fifty_move_rule(mv, &board) {
+                    1
+                } else {
+                    halfmove_clock + 1
+                };
+                board = board.make_move_new(mv);
+                path.push(node);
+                line_history.push(board.get_hash());
+            }
+            let depth = path.len() - 1;
+            sel_depth = sel_depth.max(depth);
+
+            //Expected score for the side to move at the leaf
+            let score = if let Some(score) = tree[node].terminal {
+                score
+            } else {
+                let history = line_history
+                    .iter()
+                    .rev()
+                    .chain(game_history.iter().rev())
+                    .copied();
+                let moves = MoveGen::new_legal(&board);
+                let status = board_status(&board, &moves);
+                let terminal = if depth > 0 && draw_by_move_rule(&board, history, halfmove_clock, depth as u8) {
+                    Some(0.5)
+                } else {
+                    match status {
+                        BoardStatus::Checkmate => Some(0.0),
+                        BoardStatus::Stalemate => Some(0.5),
+                        BoardStatus::Ongoing => None
+                    }
+                };
+                match terminal {
+                    Some(score) => {
+                        tree[node].terminal = Some(score);
+                        score
+                    }
+                    None => {
+                        let full = tree.len() + moves.len() > self.options.max_tree_nodes;
+                        //The root is always expanded so there's a move to report
+                        if depth > 0 && (depth >= limits.depth as usize || full) {
+                            let value = -self.options.evaluator.evaluate(&board);
+                            1.0 - self.expected_score(value)
+                        } else {
+                            self.expand(&mut tree, node, &board, moves)
+                        }
+                    }
+                }
+            };
+            //Scores alternate perspective every ply
+            let mut score = 1.0 - score;
+            for &node in path.iter().rev() {
+                tree[node].visits += 1;
+                tree[node].total += score;
+                score = 1.0 - score;
+            }
+            playouts += 1;
+
+            let full = tree.len() >= self.options.max_tree_nodes;
+            let done = only_move || playouts >= limits.nodes || full || handler.time_up();
+            if done || playouts % self.options.report_interval == 0 {
+                let line = self.principal_variation(&tree);
+                let best = &tree[line[0]];
+                let value = match best.terminal {
+                    //The opponent has been checkmated
+                    Some(0.0) => Eval::mate_in(1),
+                    _ => self.centipawns(best.mean())
+                };
+                let principal_variation = line
+                    .iter()
+                    .map(|&node| tree[node].mv.unwrap())
+                    .collect::<Vec<_>>();
+                let elapsed = search_begin.elapsed();
+                handler.search_result(SearchResult {
+                    mv: principal_variation[0],
+                    value,
+                    nodes: playouts,
+                    elapsed,
+                    nps: (playouts as u128 * 1_000_000 / elapsed.as_micros().max(1)) as u64,
+                    depth: principal_variation.len().min(u8::MAX as usize) as u8,
+                    sel_depth: sel_depth.min(u8::MAX as usize) as u8,
+                    re_searches: 0,
+                    tb_hits: 0,
+                    ponder_move: principal_variation.get(1).copied(),
+                    principal_variation,
+                    principal_variation_truncated: false,
+                    //The tree stands in for the transposition table
+                    transposition_table_size: self.options.max_tree_nodes,
+                    transposition_table_entries: tree.len(),
+                    hashfull: (tree.len() * 1000 / self.options.max_tree_nodes.max(1)) as u32,
+                    transposition_table_stats: None,
+                    easy_move: false,
+                    only_move,
+                    memory_usage: tree.capacity() * std::mem::size_of::<Node>(),
+                    #[cfg(feature = "hash-verification")]
+                    transposition_table_collisions: 0
+                });
+            }
+            if done {
+                break;
+            }
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::Instant;
+
 use chess::*;
 use arraydeque::ArrayDeque;
 
@@ -7,11 +9,50 @@ use crate::moves::*;
 use crate::oracle;
 
 mod game_helpers;
-use game_helpers::*;
+mod cuckoo;
+pub(crate) use game_helpers::*;
+pub use game_helpers::move_is_legal;
 
 mod search_defs;
 pub use search_defs::*;
 
+mod snapshot;
+
+mod rng;
+use rng::Rng;
+
+mod searcher;
+pub use searcher::*;
+
+mod mcts;
+pub use mcts::*;
+
+mod params;
+pub use params::*;
+
+///The node type we expect a node to turn out to be, based on its parent's.
+///PV nodes have an exact score, cut nodes fail high and all nodes fail low.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum NodeType {
+    Pv,
+    Cut,
+    All
+}
+
+impl NodeType {
+    fn child(self, first: bool) -> Self {
+        match self {
+            //Only the first move of a PV node is expected to be in the PV.
+            Self::Pv if first => Self::Pv,
+            //If the first move refutes the parent, it fails low.
+            Self::Cut if first => Self::All,
+            //Every move of an all node is expected to be refuted.
+            Self::All => Self::Cut,
+            _ => Self::Cut
+        }
+    }
+}
+
 trait SearchReturnType {
     type Output;
     const REQUIRES_MOVE: bool;
@@ -44,20 +85,69 @@ impl SearchReturnType for PosEval {
     }
 }
 
-pub(crate) type HistoryTable = [[[u32; NUM_SQUARES]; NUM_PIECES]; NUM_COLORS];
+///Like `PosEval`, but also returns the best move if there is one.
+struct PosEvalAndMove;
+
+impl SearchReturnType for PosEvalAndMove {
+    type Output = (Eval, Option<ChessMove>);
+    const REQUIRES_MOVE: bool = false;
+
+    fn convert(get_value: impl FnOnce() -> Eval, mv: Option<ChessMove>) -> Self::Output {
+        (get_value(), mv)
+    }
+}
+
+pub type HistoryTable = [[[u32; NUM_SQUARES]; NUM_PIECES]; NUM_COLORS];
+
+pub type PieceToHistory = [[u32; NUM_SQUARES]; NUM_PIECES];
+
+///Indexed by the side to move and the piece and destination of the previous move.
+///See `continuation_index`.
+pub(crate) type ContinuationHistoryTable = Vec<PieceToHistory>;
+
+fn continuation_index(color: Color, (piece, square): (Piece, Square)) -> usize {
+    (color.to_index() * NUM_PIECES + piece.to_index()) * NUM_SQUARES + square.to_index()
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+struct SearchStackEntry {
+    ///The hash of the position at this ply, for repetition detection
+    hash: u64,
+    ///The piece moved at this ply and its destination
+    moved: Option<(Piece, Square)>,
+    ///The static evaluation at this ply, or `None` when in check
+    static_eval: Option<Eval>,
+    ///Set for a null move verification search, which may not itself try a null move
+    null_move_verification: bool
+}
 
-pub(crate) type KillerTableEntry = ArrayDeque<[ChessMove; 2], arraydeque::Wrapping>;
+pub type KillerTableEntry = ArrayDeque<[ChessMove; 2], arraydeque::Wrapping>;
 
 pub struct LunaticSearchState<H> {
     handler: H,
     board: Board,
-    history: Vec<u64>,
+    ///Hashes of the positions since the last irreversible move, up to and including the root.
+    ///Positions inside the search are kept in the search stack instead.
+    game_history: Vec<u64>,
     halfmove_clock: u8,
     options: SearchOptions,
     cache_table: TranspositionTable,
     killer_table: Vec<KillerTableEntry>,
     history_table: HistoryTable,
-    sel_depth: u8
+    continuation_history: ContinuationHistoryTable,
+    search_stack: Vec<SearchStackEntry>,
+    ///Triangular principal variation table. Each ply holds the best line found from that ply.
+    pv_table: Vec<Vec<ChessMove>>,
+    rng: Rng,
+    sel_depth: u8,
+    re_searches: u32,
+    tb_hits: u64,
+    easy_move: Option<ChessMove>,
+    reported_result: bool,
+    ///The iteration to start from, which is non-zero when resuming a snapshot
+    start_depth: u8,
+    ///Every completed iteration, including those restored from a snapshot
+    iterations: Vec<IterationResult>
 }
 
 impl<H: LunaticHandler> LunaticSearchState<H> {
@@ -67,83 +157,154 @@ impl<H: LunaticHandler> LunaticSearchState<H> {
         moves: impl IntoIterator<Item=ChessMove>,
         options: SearchOptions
     ) -> Self {
-        //100 for history, +32 for quiescence search
-        let mut history = Vec::with_capacity(100 + options.max_depth as usize + 32);
-        let mut board = *init_pos;
-        history.push(board.get_hash());
-        for mv in moves {
-            if move_resets_fifty_move_rule(mv, &board) {
-                history.clear();
-            }
-            board = board.make_move_new(mv);
-            history.push(board.get_hash());
+        let table = TranspositionTable::with_rounded_size(options.transposition_table_size);
+        Self::with_table(handler, init_pos, moves, options, table)
+    }
+
+    ///Like `new`, but continues with the transposition table of earlier searches,
+    ///whatever its size.
+    pub fn with_table(
+        handler: H,
+        init_pos: &Board,
+        moves: impl IntoIterator<Item=ChessMove>,
+        mut options: SearchOptions,
+        mut cache_table: TranspositionTable
+    ) -> Self {
+        cache_table.new_search();
+        let mut game_plies = 0;
+        let moves = moves.into_iter().inspect(|_| game_plies += 1);
+        let (board, history) = play_moves(init_pos, moves);
+        //Material only goes down from the root, so the whole search stays in the endgame.
+        if non_pawn_material(&board, options.evaluator.standard()) <= options.endgame_material as i32 {
+            options.max_depth = options.max_depth.max(options.endgame_max_depth);
+            options.evaluator.set_light(options.endgame_light_eval);
         }
         let halfmove_clock = history.len() as u8 - 1;
+        //Opening variety works like a handicap that wears off.
+        if game_plies < options.random_opening_plies as usize {
+            options.handicap_margin = options.handicap_margin.max(options.random_opening_margin);
+        }
+        if let Some(root_moves) = &mut options.root_moves {
+            root_moves.retain(|&mv| board.legal(mv));
+            if root_moves.is_empty() {
+                options.root_moves = None;
+            }
+        }
 
         Self {
             handler,
             board,
-            history,
+            game_history: history,
             halfmove_clock,
-            cache_table: TranspositionTable::with_rounded_size(options.transposition_table_size),
-            killer_table: vec![KillerTableEntry::new(); options.max_depth as usize],
+            cache_table,
+            //Extensions can take the search past max depth, so cover every ply.
+            killer_table: vec![KillerTableEntry::new(); u8::MAX as usize + 1],
             history_table: [[[0; NUM_SQUARES]; NUM_PIECES]; NUM_COLORS],
+            continuation_history: vec![[[0; NUM_SQUARES]; NUM_PIECES]; NUM_COLORS * NUM_PIECES * NUM_SQUARES],
+            search_stack: vec![SearchStackEntry::default(); u8::MAX as usize + 1],
+            //+1 so the deepest ply still has a child line to copy from.
+            pv_table: vec![Vec::new(); u8::MAX as usize + 2],
+            rng: Rng::new(options.handicap_seed),
             options,
-            sel_depth: 0
+            sel_depth: 0,
+            re_searches: 0,
+            tb_hits: 0,
+            easy_move: None,
+            reported_result: false,
+            start_depth: 0,
+            iterations: Vec::new()
         }
     }
 
-    pub fn search(&mut self) {
-        let history_len = self.history.len();
+    pub fn iterations(&self) -> &[IterationResult] {
+        &self.iterations
+    }
+
+    ///Gives back the transposition table, for use by later searches.
+    pub fn into_table(self) -> TranspositionTable {
+        self.cache_table
+    }
 
+    pub fn search(&mut self) {
+        let search_begin = Instant::now();
         let mut nodes = 0;
-        for depth in 0..self.options.max_depth {
+        //With only one legal move there's nothing to think about.
+        let only_move = MoveGen::new_legal(&self.board)
+            .filter(|&mv| self.root_move_allowed(mv))
+            .count() == 1;
+        let eval_state = self.options.evaluator.eval_state(&self.board);
+        for depth in self.start_depth..self.options.max_depth {
             let result = self.search_position::<BestMove>(
                 &self.board.clone(),
+                &eval_state,
                 &mut nodes,
                 depth,
                 0,
                 self.halfmove_clock,
+                NodeType::Pv,
                 Eval::MIN,
                 Eval::MAX
             );
-            //Early termination may trash history, so restore the state.
-            self.history.truncate(history_len);
             match result {
                 Ok(Some((mv, value))) => {
-                    let mut principal_variation = Vec::new();
-                    let mut board = self.board;
-                    let mut halfmove_clock = self.halfmove_clock;
-    
-                    let mut next_move = Some(mv);
-                    while let Some(mv) = next_move.take() {
-                        halfmove_clock = if move_resets_fifty_move_rule(mv, &board) {
-                            1
-                        } else {
-                            halfmove_clock + 1
-                        };
-                        board = board.make_move_new(mv);
-                        principal_variation.push(mv);
-                        self.history.push(board.get_hash());
-    
-                        next_move = if draw_by_move_rule(&board, &self.history, halfmove_clock) {
-                            None
-                        } else {
-                            self.cache_table.get(&board).map(|e| e.best_move)
-                        };
+                    let mut principal_variation = self.pv_table[0].clone();
+                    let max_length = self.options.max_principal_variation_length as usize;
+                    let principal_variation_truncated = principal_variation.len() > max_length;
+                    principal_variation.truncate(max_length);
+                    let ponder_move = self.pv_table[0].get(1).copied().or_else(|| {
+                        let child_board = self.board.make_move_new(mv);
+                        self.cache_table
+                            .get(&child_board)
+                            .and_then(|entry| entry.best_move)
+                            .filter(|&reply| move_is_legal(&child_board, reply))
+                    });
+
+                    let is_centipawn = matches!(value.kind(), EvalKind::Centipawn(_));
+                    if depth == self.options.easy_move_depth && depth > 0 && is_centipawn {
+                        let easy_move = self.easy_move_scout(mv, value, depth, &eval_state, &mut nodes);
+                        match easy_move {
+                            Ok(true) => self.easy_move = Some(mv),
+                            Ok(false) => {},
+                            Err(()) => break //Terminated
+                        }
                     }
-                    self.history.truncate(history_len);
                     
+                    //A resumed search repeats the last iteration it restored
+                    self.iterations.retain(|iteration| iteration.depth < depth);
+                    self.iterations.push(IterationResult {
+                        depth,
+                        mv,
+                        value,
+                        principal_variation: principal_variation.clone()
+                    });
+                    let elapsed = search_begin.elapsed();
                     self.handler.search_result(SearchResult {
                         mv,
                         value,
                         nodes,
+                        elapsed,
+                        nps: (nodes as u128 * 1_000_000 / elapsed.as_micros().max(1)) as u64,
                         depth,
                         sel_depth: self.sel_depth,
+                        re_searches: self.re_searches,
+                        tb_hits: self.tb_hits,
                         principal_variation,
+                        principal_variation_truncated,
+                        ponder_move,
                         transposition_table_size: self.cache_table.capacity(),
                         transposition_table_entries: self.cache_table.len(),
+                        hashfull: self.cache_table.hashfull(),
+                        transposition_table_stats: Some(self.cache_table.stats()),
+                        easy_move: self.easy_move == Some(mv),
+                        only_move,
+                        memory_usage: self.memory_usage(),
+                        #[cfg(feature = "hash-verification")]
+                        transposition_table_collisions: self.cache_table.collisions()
                     });
+                    self.reported_result = true;
+                    if only_move {
+                        break;
+                    }
                 },
                 Ok(None) => {},
                 Err(()) => break //Terminated
@@ -151,30 +312,168 @@ impl<H: LunaticHandler> LunaticSearchState<H> {
         }
     }
     
+    fn memory_usage(&self) -> usize {
+        use std::mem::size_of;
+
+        self.cache_table.memory_usage() +
+            self.killer_table.capacity() * size_of::<KillerTableEntry>() +
+            size_of::<HistoryTable>() +
+            self.continuation_history.capacity() * size_of::<PieceToHistory>() +
+            self.search_stack.capacity() * size_of::<SearchStackEntry>() +
+            self.pv_table.iter().map(|line| size_of::<Vec<ChessMove>>() + line.capacity() * size_of::<ChessMove>()).sum::<usize>() +
+            self.game_history.capacity() * size_of::<u64>()
+    }
+
+    ///Hashes of the positions in the current line followed by the game,
+    ///most recent first, starting with the position at this ply.
+    fn position_history(&self, ply_index: u8) -> impl Iterator<Item=u64> + Clone + '_ {
+        let line = self.search_stack[1..=ply_index as usize]
+            .iter()
+            .rev()
+            .map(|entry| entry.hash);
+        line.chain(self.game_history.iter().rev().copied())
+    }
+
+    ///Checks the fifty move rule and repetitions of earlier
+    ///positions in both the game and the current line.
+    fn is_draw(&self, board: &Board, ply_index: u8, halfmove_clock: u8) -> bool {
+        draw_by_move_rule(board, self.position_history(ply_index), halfmove_clock, ply_index)
+    }
+
+    ///Can the side to move force a repetition with its next move?
+    ///Positions before a null move can't be reached again, so they're not considered.
+    fn upcoming_repetition(&self, board: &Board, ply_index: u8, halfmove_clock: u8) -> bool {
+        let plies_since_null = (0..ply_index)
+            .rev()
+            .position(|ply| self.search_stack[ply as usize].moved.is_none())
+            .map_or(u8::MAX, |plies| plies as u8 + 1);
+        cuckoo::upcoming_repetition(
+            board,
+            self.position_history(ply_index),
+            halfmove_clock.min(plies_since_null),
+            ply_index
+        )
+    }
+
+    fn root_move_allowed(&self, mv: ChessMove) -> bool {
+        self.options.root_moves.as_ref().is_none_or(|root_moves| root_moves.contains(&mv))
+    }
+
+    ///The null move reduction grows with depth and with how far
+    ///the static evaluation is above beta.
+    fn null_move_reduction(&self, static_eval: Eval, depth: u8, beta: Eval) -> u8 {
+        let depth_reduction = depth / self.options.null_move_depth_divisor.max(1);
+        let eval_reduction = match (static_eval.kind(), beta.kind()) {
+            (EvalKind::Centipawn(eval), EvalKind::Centipawn(beta)) if eval > beta => {
+                let margin = self.options.null_move_eval_margin.max(1) as i32;
+                ((eval as i32 - beta as i32) / margin).min(3) as u8
+            }
+            _ => 0
+        };
+        self.options.null_move_reduction + depth_reduction + eval_reduction
+    }
+
+    ///Is the static evaluation better than at our previous move?
+    ///Never while in check, and always if there's nothing to compare against.
+    fn improving(&self, ply_index: u8, static_eval: Option<Eval>) -> bool {
+        let previous = ply_index
+            .checked_sub(2)
+            .map(|ply| self.search_stack[ply as usize].static_eval);
+        match (static_eval, previous) {
+            (None, _) => false,
+            (Some(eval), Some(Some(previous))) => eval > previous,
+            _ => true
+        }
+    }
+
+    ///Checks if every root move other than the best move fails
+    ///low against the best value lowered by the easy move margin.
+    fn easy_move_scout(
+        &mut self,
+        best_move: ChessMove,
+        best_value: Eval,
+        depth: u8,
+        eval_state: &EvalState,
+        node_count: &mut u64
+    ) -> Result<bool, ()> {
+        let threshold = best_value - Eval::cp(self.options.easy_move_margin);
+        let board = self.board;
+        for mv in MoveGen::new_legal(&board) {
+            if mv == best_move || !self.root_move_allowed(mv) {
+                continue;
+            }
+            let child_board = board.make_move_new(mv);
+            let halfmove_clock = if move_resets_fifty_move_rule(mv, &board) {
+                1
+            } else {
+                self.halfmove_clock + 1
+            };
+            self.search_stack[0].moved = Some((board.piece_on(mv.get_source()).unwrap(), mv.get_dest()));
+            let child_eval_state = self.options.evaluator.update_eval_state(eval_state, &board, mv);
+            let child_value = -self.search_position::<PosEval>(
+                &child_board,
+                &child_eval_state,
+                node_count,
+                depth - 1,
+                1,
+                halfmove_clock,
+                NodeType::Cut,
+                -threshold,
+                -(threshold - Eval::cp(1))
+            )?;
+            if child_value >= threshold {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn search_position<T: SearchReturnType>(
         &mut self,
         board: &Board,
-        node_count: &mut u32,
+        eval_state: &EvalState,
+        node_count: &mut u64,
         mut depth: u8,
         ply_index: u8,
         halfmove_clock: u8,
+        node_type: NodeType,
         mut alpha: Eval,
         mut beta: Eval
     ) -> Result<T::Output, ()> {
         self.sel_depth = self.sel_depth.max(ply_index);
-        let original_alpha = alpha;
+        let mut original_alpha = alpha;
+        let pv_node = alpha + Eval::cp(1) < beta;
+        let null_move_verification = std::mem::take(
+            &mut self.search_stack[ply_index as usize].null_move_verification
+        );
+        self.pv_table[ply_index as usize].clear();
 
-        if !T::REQUIRES_MOVE && *node_count % 4096 == 0 && self.handler.time_up() {
+        if !T::REQUIRES_MOVE && node_count.is_multiple_of(4096) && self.handler.time_up() {
+            return Err(());
+        }
+        //The node limit only applies once there's a move to play.
+        if !T::REQUIRES_MOVE && *node_count >= self.options.max_nodes && self.reported_result {
             return Err(());
         }
 
         *node_count += 1;
 
-        if !T::REQUIRES_MOVE && draw_by_move_rule(board, &self.history, halfmove_clock) {
+        self.search_stack[ply_index as usize].hash = board.get_hash();
+        if !T::REQUIRES_MOVE && self.is_draw(board, ply_index, halfmove_clock) {
             return Ok(T::convert(|| Eval::DRAW, None));
         }
+        //If we can force a repetition, this position is at least a draw.
+        if !T::REQUIRES_MOVE && alpha < Eval::DRAW &&
+            self.upcoming_repetition(board, ply_index, halfmove_clock) {
+            alpha = Eval::DRAW;
+            original_alpha = alpha;
+            if alpha >= beta {
+                return Ok(T::convert(|| alpha, None));
+            }
+        }
 
-        let moves = MoveGen::new_legal(&board);
+        let moves = MoveGen::new_legal(board);
         let status = board_status(board, &moves);
         if status != BoardStatus::Ongoing {
             let eval = if status == BoardStatus::Checkmate {
@@ -187,6 +486,7 @@ impl<H: LunaticHandler> LunaticSearchState<H> {
 
         if !T::REQUIRES_MOVE {
             if let Some(eval) = oracle::oracle(board) {
+                self.tb_hits += 1;
                 return Ok(T::convert(|| eval, None));
             }
         }
@@ -198,16 +498,30 @@ impl<H: LunaticHandler> LunaticSearchState<H> {
             depth += 1;
         }
 
-        if let Some(entry) = self.cache_table.get(&board) {
-            //Larger subtree means deeper search
-            if entry.depth >= depth {
+        //Hash collisions can produce entries with moves that are illegal here.
+        let entry = self.cache_table
+            .get(board)
+            .filter(|entry| entry.best_move.is_none_or(|mv| move_is_legal(board, mv)));
+        let mut table_move = entry.and_then(|entry| entry.best_move);
+        //Root entries may also come from searches without the root move restriction.
+        let entry = entry
+            .filter(|_| ply_index > 0 || self.options.root_moves.is_none())
+            .map(|entry| TableEntry {
+                value: entry.value.to_root_relative(ply_index),
+                ..entry
+            });
+        if let Some(entry) = entry {
+            //Larger subtree means deeper search.
+            //The root always searches, since a cutoff there has no principal variation,
+            //which happens with a table kept from earlier searches.
+            if entry.depth >= depth && ply_index > 0 {
                 match entry.kind {
-                    TableEntryKind::Exact => return Ok(T::convert(|| entry.value, Some(entry.best_move))),
+                    TableEntryKind::Exact => return Ok(T::convert(|| entry.value, entry.best_move)),
                     TableEntryKind::LowerBound => alpha = alpha.max(entry.value),
                     TableEntryKind::UpperBound => beta = beta.min(entry.value)
                 }
                 if alpha >= beta {
-                    return Ok(T::convert(|| entry.value, Some(entry.best_move)));
+                    return Ok(T::convert(|| entry.value, entry.best_move));
                 }
             }
         }
@@ -219,9 +533,11 @@ impl<H: LunaticHandler> LunaticSearchState<H> {
                     *node_count -= 1;
                     self.quiescence_search(
                         board,
+                        eval_state,
                         node_count,
                         ply_index,
                         halfmove_clock,
+                        self.options.quiescence_checks,
                         alpha,
                         beta
                     )
@@ -230,9 +546,17 @@ impl<H: LunaticHandler> LunaticSearchState<H> {
             ))
         }
 
+        let static_eval = if in_check {
+            None
+        } else {
+            let static_eval = self.options.evaluator.evaluate_with_state(board, eval_state);
+            Some(self.options.evaluator.scale_for_halfmove_clock(static_eval, halfmove_clock))
+        };
+        self.search_stack[ply_index as usize].static_eval = static_eval;
+        let improving = self.improving(ply_index, static_eval);
+
         let mut value = Eval::MIN;
         let mut best_move = None;
-        let killers = self.killer_table[ply_index as usize].clone();
         let ally_pieces = *board.color_combined(board.side_to_move());
         let sliding_pieces = 
             *board.pieces(Piece::Rook) |
@@ -240,79 +564,175 @@ impl<H: LunaticHandler> LunaticSearchState<H> {
             *board.pieces(Piece::Queen);
 
         //If I have at least one sliding piece...
-        if self.options.null_move_pruning && ally_pieces & sliding_pieces != EMPTY {
-            if let Some(child_board) = board.null_move() {
+        if self.options.null_move_pruning && !null_move_verification &&
+            ally_pieces & sliding_pieces != EMPTY {
+            //Passing is unlikely to fail high if we're already below beta.
+            let null_move = static_eval
+                .filter(|&static_eval| static_eval >= beta)
+                .and_then(|static_eval| Some((board.null_move()?, static_eval)));
+            if let Some((child_board, static_eval)) = null_move {
                 let narrowed_alpha = beta - Eval::cp(1);
-                self.history.push(child_board.get_hash());
+                let reduction = self.null_move_reduction(static_eval, depth, beta);
+                self.search_stack[ply_index as usize].moved = None;
                 let child_value = -self.search_position::<PosEval>(
                     &child_board,
+                    eval_state,
                     node_count,
-                    depth.saturating_sub(self.options.null_move_reduction + 1),
+                    depth.saturating_sub(reduction + 1),
                     ply_index + 1,
                     halfmove_clock + 1,
+                    NodeType::All,
                     -beta,
                     -narrowed_alpha
                 )?;
-                self.history.pop();
                 if child_value >= beta {
-                    return Ok(T::convert(|| child_value, None));
+                    if depth < self.options.null_move_verification_depth {
+                        return Ok(T::convert(|| child_value, None));
+                    }
+                    //Verify the cutoff with a reduced search of our own moves.
+                    self.search_stack[ply_index as usize].null_move_verification = true;
+                    let (verified_value, verified_move) = self.search_position::<PosEvalAndMove>(
+                        board,
+                        eval_state,
+                        node_count,
+                        depth.saturating_sub(reduction + 1),
+                        ply_index,
+                        halfmove_clock,
+                        NodeType::Cut,
+                        narrowed_alpha,
+                        beta
+                    )?;
+                    if verified_value >= beta {
+                        return Ok(T::convert(|| verified_value, None));
+                    }
+                    table_move = verified_move.or(table_move);
                 }
             }
         }
+
+        //Internal iterative deepening.
+        //Without a hash move, a shallower search provides one for move ordering.
+        let has_table_move = table_move.is_some();
+        if self.options.internal_iterative_deepening && pv_node && !has_table_move &&
+            depth >= self.options.iid_min_depth {
+            //The search at the same ply extends checks again, so leave the extension out.
+            let extension = in_check as u8;
+            let (_, iid_move) = self.search_position::<PosEvalAndMove>(
+                board,
+                eval_state,
+                node_count,
+                depth.saturating_sub(self.options.iid_reduction + extension).max(1),
+                ply_index,
+                halfmove_clock,
+                node_type,
+                alpha,
+                beta
+            )?;
+            table_move = iid_move;
+        }
+        //Internal iterative reduction.
+        //Expected cut nodes without a hash move are likely not worth a full depth search.
+        if self.options.internal_iterative_reduction && node_type == NodeType::Cut &&
+            !has_table_move && depth >= self.options.iid_min_depth {
+            depth -= 1;
+        }
+
+        let killers = self.killer_table[ply_index as usize].clone();
         let mut moves = SortedMoveGenerator::new(
-            &self.cache_table,
-            killers, 
+            table_move,
+            killers,
             *board,
             moves
         );
+        if ply_index == 0 {
+            if let Some(root_moves) = &self.options.root_moves {
+                moves.retain(|mv| root_moves.contains(&mv));
+            }
+        }
+        //Internal iterative deepening and null move verification may have left a line here.
+        self.pv_table[ply_index as usize].clear();
         let mut index = 0;
-        while let Some(mv) = moves.next(&self.history_table) {
+        let previous_move = ply_index
+            .checked_sub(1)
+            .and_then(|ply| self.search_stack[ply as usize].moved);
+        let continuation_index = previous_move.map(|mv| continuation_index(board.side_to_move(), mv));
+        while let Some(mv) = moves.next(
+            &self.history_table,
+            continuation_index.map(|index| &self.continuation_history[index])
+        ) {
             let child_board = board.make_move_new(mv);
-            let quiet = move_is_quiet(&board, &child_board);
+            let moved_piece = board.piece_on(mv.get_source()).unwrap();
+            let quiet = move_is_quiet(board, &child_board);
             let gives_check = *child_board.checkers() != EMPTY;
             let halfmove_clock = if move_resets_fifty_move_rule(mv, board) {
                 1
             } else {
                 halfmove_clock + 1
             };
-            let mut reduced_depth = depth;
-            let mut narrowed_beta = beta;
-            if index as u8 >= self.options.late_move_leeway && depth > 3 &&
-                quiet && !in_check && !gives_check {
-                reduced_depth = if self.options.late_move_reduction < depth {
-                    depth - self.options.late_move_reduction
+            //Passed pawn extensions. These pushes are quiet but rarely safe to reduce.
+            let passed_pawn_push = self.options.passed_pawn_extension &&
+                is_passed_pawn_push(board, mv);
+            let extension = passed_pawn_push as u8;
+            //SEE pruning. At low depth, moves that lose too much material are skipped.
+            let have_move = best_move.is_some() && !matches!(value.kind(), EvalKind::MatedIn(_));
+            if self.options.see_pruning && ply_index > 0 && have_move &&
+                depth <= self.options.see_pruning_max_depth &&
+                !in_check && !gives_check && !passed_pawn_push && mv.get_promotion().is_none() {
+                let threshold = if quiet {
+                    self.options.see_pruning_quiet_margin as i32 * depth as i32 * depth as i32
                 } else {
-                    1
+                    self.options.see_pruning_capture_margin as i32 * depth as i32
                 };
-                narrowed_beta = alpha + Eval::cp(1);
-            }
-            self.history.push(child_board.get_hash());
-            let mut child_value;
-            loop {
-                child_value = -self.search_position::<PosEval>(
-                    &child_board,
-                    node_count,
-                    reduced_depth - 1,
-                    ply_index + 1,
-                    halfmove_clock,
-                    -narrowed_beta,
-                    -alpha
-                )?;
-
-                //If it was searched to a reduced depth and it
-                //increased alpha, search again with full depth
-                if reduced_depth < depth && child_value > alpha {
-                    reduced_depth = depth;
-                    narrowed_beta = beta;
+                let threshold = Eval::cp(-(threshold.min(i16::MAX as i32) as i16));
+                if static_exchange_evaluation(board, mv) < threshold {
+                    index += 1;
                     continue;
                 }
-                break;
             }
-            self.history.pop();
+            let mut reduction = 0;
+            if index as u8 >= self.options.late_move_leeway && depth > 3 &&
+                quiet && !in_check && !gives_check && !passed_pawn_push {
+                reduction = self.options.late_move_reduction;
+                //Late moves are even less likely to matter when our position is getting worse.
+                if !improving {
+                    reduction += 1;
+                }
+                //Or when a move before them was already expected to refute the parent.
+                if node_type == NodeType::Cut {
+                    reduction += 1;
+                }
+            }
+            //Handicap: Root moves within the margin of alpha get exact scores plus noise.
+            let handicap_margin = match alpha.kind() {
+                EvalKind::Centipawn(_) if ply_index == 0 => self.options.handicap_margin.max(0),
+                _ => 0
+            };
+            self.search_stack[ply_index as usize].moved = Some((moved_piece, mv.get_dest()));
+            let child_eval_state = self.options.evaluator.update_eval_state(eval_state, board, mv);
+            let mut child_value = self.search_child(
+                &child_board,
+                &child_eval_state,
+                node_count,
+                depth - 1 + extension,
+                reduction,
+                ply_index + 1,
+                halfmove_clock,
+                node_type.child(index == 0),
+                alpha - Eval::cp(handicap_margin),
+                beta
+            )?;
+            if handicap_margin > 0 {
+                if let EvalKind::Centipawn(_) = child_value.kind() {
+                    child_value += Eval::cp(self.rng.up_to(handicap_margin as u16) as i16);
+                }
+            }
             if child_value > value || best_move.is_none() {
                 value = child_value;
                 best_move = Some(mv);
             }
+            if child_value > alpha {
+                self.update_principal_variation(ply_index, mv);
+            }
             alpha = alpha.max(value);
             if alpha >= beta {
                 if quiet {
@@ -321,9 +741,15 @@ impl<H: LunaticHandler> LunaticSearchState<H> {
                     entry.push_back(mv);
                     self.history_table
                         [board.side_to_move().to_index()]
-                        [board.piece_on(mv.get_source()).unwrap().to_index()]
+                        [moved_piece.to_index()]
                         [mv.get_dest().to_index()]
                         += depth as u32 * depth as u32;
+                    if let Some(index) = continuation_index {
+                        self.continuation_history[index]
+                            [moved_piece.to_index()]
+                            [mv.get_dest().to_index()]
+                            += depth as u32 * depth as u32;
+                    }
                 }
                 break;
             }
@@ -331,37 +757,113 @@ impl<H: LunaticHandler> LunaticSearchState<H> {
         }
         let best_move = best_move.unwrap();
         self.cache_table.set(
-            &board,
+            board,
             TableEntry {
                 kind: match value {
                     _ if value <= original_alpha => TableEntryKind::UpperBound,
                     _ if value >= beta => TableEntryKind::LowerBound,
                     _ => TableEntryKind::Exact
                 },
-                value,
+                value: value.to_node_relative(ply_index),
                 depth,
-                best_move
+                best_move: Some(best_move)
             }
         );
         Ok(T::convert(|| value, Some(best_move)))
     }
 
+    ///The line at this ply becomes the move followed by the child's line.
+    fn update_principal_variation(&mut self, ply_index: u8, mv: ChessMove) {
+        let (lines, child_lines) = self.pv_table.split_at_mut(ply_index as usize + 1);
+        let line = &mut lines[ply_index as usize];
+        line.clear();
+        line.push(mv);
+        line.extend_from_slice(&child_lines[0]);
+    }
+
+    ///Searches a child with a null window at a reduced depth first,
+    ///then searches again with the full window and depth if it beats alpha.
+    ///Without a reduction, the child is searched with the full window directly.
+    #[allow(clippy::too_many_arguments)]
+    fn search_child(
+        &mut self,
+        child_board: &Board,
+        child_eval_state: &EvalState,
+        node_count: &mut u64,
+        depth: u8,
+        reduction: u8,
+        ply_index: u8,
+        halfmove_clock: u8,
+        node_type: NodeType,
+        alpha: Eval,
+        beta: Eval
+    ) -> Result<Eval, ()> {
+        if reduction > 0 {
+            let scout_value = -self.search_position::<PosEval>(
+                child_board,
+                child_eval_state,
+                node_count,
+                depth.saturating_sub(reduction),
+                ply_index,
+                halfmove_clock,
+                node_type,
+                -(alpha + Eval::cp(1)),
+                -alpha
+            )?;
+            if scout_value <= alpha {
+                return Ok(scout_value);
+            }
+            self.re_searches += 1;
+        }
+        Ok(-self.search_position::<PosEval>(
+            child_board,
+            child_eval_state,
+            node_count,
+            depth,
+            ply_index,
+            halfmove_clock,
+            node_type,
+            -beta,
+            -alpha
+        )?)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn quiescence_search(
         &mut self,
         board: &Board,
-        node_count: &mut u32,
+        eval_state: &EvalState,
+        node_count: &mut u64,
         ply_index: u8,
         halfmove_clock: u8,
+        checks: QuiescenceChecks,
         mut alpha: Eval,
         mut beta: Eval
     ) -> Eval {
         *node_count += 1;
+        let mut original_alpha = alpha;
 
-        if draw_by_move_rule(board, &self.history, halfmove_clock) {
+        self.search_stack[ply_index as usize].hash = board.get_hash();
+        if self.is_draw(board, ply_index, halfmove_clock) {
             return Eval::DRAW;
         }
+        if alpha < Eval::DRAW && self.upcoming_repetition(board, ply_index, halfmove_clock) {
+            alpha = Eval::DRAW;
+            original_alpha = alpha;
+            if alpha >= beta {
+                return alpha;
+            }
+        }
 
-        if let Some(entry) = self.cache_table.get(&board) {
+        let entry = if self.options.quiescence_table_reads {
+            self.cache_table.get(board).map(|entry| TableEntry {
+                value: entry.value.to_root_relative(ply_index),
+                ..entry
+            })
+        } else {
+            None
+        };
+        if let Some(entry) = entry {
             //Literally any hit is better than quiescence search
             match entry.kind {
                 TableEntryKind::Exact => return entry.value,
@@ -374,13 +876,21 @@ impl<H: LunaticHandler> LunaticSearchState<H> {
         }
 
 
-        let moves = MoveGen::new_legal(&board);
+        let moves = MoveGen::new_legal(board);
         match board_status(board, &moves) {
             BoardStatus::Checkmate => return Eval::mated_in(ply_index),
             BoardStatus::Stalemate => return Eval::DRAW,
             _ => {}
         }
-        let mut value = EVALUATOR.evaluate(board);
+        let in_check = *board.checkers() != EMPTY;
+        let mut value = if in_check {
+            //There's no standing pat while in check.
+            //All evasions are searched instead.
+            Eval::MIN
+        } else {
+            let static_eval = self.options.evaluator.evaluate_with_state(board, eval_state);
+            self.options.evaluator.scale_for_halfmove_clock(static_eval, halfmove_clock)
+        };
         //The reason we are allowed to safely return this score
         //is the assumption that even though we only check captures,
         //at any point in the search there is at least one other
@@ -389,37 +899,57 @@ impl<H: LunaticHandler> LunaticSearchState<H> {
         //least that value.
         if value > alpha {
             alpha = value;
-            if alpha >= beta {
-                return value;
-            }
         }
-        for mv in quiescence_move_generator(&board, moves) {
-            let child_board = board.make_move_new(mv);
-            let halfmove_clock = if move_resets_fifty_move_rule(mv, board) {
-                1
-            } else {
-                halfmove_clock + 1
-            };
-            self.history.push(child_board.get_hash());
-            let child_value = -self.quiescence_search(
-                &child_board,
-                node_count,
-                ply_index + 1,
-                halfmove_clock,
-                -beta,
-                -alpha
-            );
-            self.history.pop();
-            if child_value > value {
-                value = child_value;
-                if value > alpha {
-                    alpha = value;
-                    if alpha >= beta {
-                        return value;
+        let mut best_move = None;
+        if alpha < beta {
+            let prune_losing = self.options.quiescence_see_pruning;
+            for mv in quiescence_move_generator(board, moves, prune_losing, checks) {
+                let child_board = board.make_move_new(mv);
+                let halfmove_clock = if move_resets_fifty_move_rule(mv, board) {
+                    1
+                } else {
+                    halfmove_clock + 1
+                };
+                self.search_stack[ply_index as usize].moved = Some((board.piece_on(mv.get_source()).unwrap(), mv.get_dest()));
+                let child_eval_state = self.options.evaluator.update_eval_state(eval_state, board, mv);
+                //Quiet checks are only generated at the first ply
+                let child_value = -self.quiescence_search(
+                    &child_board,
+                    &child_eval_state,
+                    node_count,
+                    ply_index + 1,
+                    halfmove_clock,
+                    QuiescenceChecks::None,
+                    -beta,
+                    -alpha
+                );
+                if child_value > value {
+                    value = child_value;
+                    best_move = Some(mv);
+                    if value > alpha {
+                        alpha = value;
+                        if alpha >= beta {
+                            break;
+                        }
                     }
                 }
             }
         }
+        if self.options.quiescence_table_writes {
+            self.cache_table.set(
+                board,
+                TableEntry {
+                    kind: match value {
+                        _ if value <= original_alpha => TableEntryKind::UpperBound,
+                        _ if value >= beta => TableEntryKind::LowerBound,
+                        _ => TableEntryKind::Exact
+                    },
+                    value: value.to_node_relative(ply_index),
+                    depth: 0,
+                    best_move
+                }
+            );
+        }
         value
     }
 }
@@ -0,0 +1,209 @@
+use std::fmt::{Display, Formatter};
+
+use crate::moves::QuiescenceChecks;
+use super::SearchOptions;
+
+///A numeric search constant that can be set by name at runtime,
+///so external tuners such as SPSA workers can tune it without recompiling.
+///Every parameter is also a UCI option.
+#[derive(Debug, Clone, Copy)]
+pub struct TunableParam {
+    ///The parameter's name, which is also its UCI option name
+    pub name: &'static str,
+    pub min: i64,
+    pub max: i64,
+    ///A sensible initial step size for tuners
+    pub step: i64,
+    get: fn(&SearchOptions) -> i64,
+    set: fn(&mut SearchOptions, i64)
+}
+
+impl TunableParam {
+    pub fn get(&self, options: &SearchOptions) -> i64 {
+        (self.get)(options)
+    }
+
+    pub fn set(&self, options: &mut SearchOptions, value: i64) -> Result<(), ParamError> {
+        if value < self.min || value > self.max {
+            return Err(ParamError::OutOfRange {
+                name: self.name,
+                value,
+                min: self.min,
+                max: self.max
+            });
+        }
+        (self.set)(options, value);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamError {
+    UnknownParam(String),
+    OutOfRange {
+        name: &'static str,
+        value: i64,
+        min: i64,
+        max: i64
+    }
+}
+
+impl Display for ParamError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownParam(name) => write!(f, "unknown parameter {:?}", name),
+            Self::OutOfRange { name, value, min, max } => write!(
+                f,
+                "{} is out of range for {} ({} to {})",
+                value, name, min, max
+            )
+        }
+    }
+}
+
+impl std::error::Error for ParamError {}
+
+///Converts a range checked parameter value to the type of its field.
+trait FromParam {
+    fn from_param(value: i64) -> Self;
+}
+
+macro_rules! int_from_param {
+    ($($ty:ty)*) => {
+        $(impl FromParam for $ty {
+            fn from_param(value: i64) -> Self {
+                value as Self
+            }
+        })*
+    }
+}
+
+int_from_param!(u8 i16);
+
+impl FromParam for QuiescenceChecks {
+    fn from_param(value: i64) -> Self {
+        match value {
+            0 => Self::None,
+            1 => Self::All,
+            _ => Self::NonLosing
+        }
+    }
+}
+
+macro_rules! tunable_params {
+    ($($name:literal => $field:ident: $min:expr, $max:expr, $step:expr;)*) => {
+        ///Every tunable parameter, in a stable order.
+        pub const TUNABLE_PARAMS: &[TunableParam] = &[$(
+            TunableParam {
+                name: $name,
+                min: $min,
+                max: $max,
+                step: $step,
+                get: |options| options.$field as i64,
+                set: |options, value| options.$field = FromParam::from_param(value)
+            }
+        ),*];
+    }
+}
+
+tunable_params! {
+    "Late Move Reduction" => late_move_reduction: 0, 8, 1;
+    "Late Move Leeway" => late_move_leeway: 0, 32, 1;
+    "Null Move Reduction" => null_move_reduction: 0, 8, 1;
+    "Null Move Depth Divisor" => null_move_depth_divisor: 1, 16, 1;
+    "Null Move Eval Margin" => null_move_eval_margin: 1, 1000, 20;
+    "Null Move Verification Depth" => null_move_verification_depth: 0, u8::MAX as i64, 1;
+    "IID Min Depth" => iid_min_depth: 0, u8::MAX as i64, 1;
+    "IID Reduction" => iid_reduction: 0, u8::MAX as i64, 1;
+    "SEE Pruning Max Depth" => see_pruning_max_depth: 0, u8::MAX as i64, 1;
+    "SEE Pruning Quiet Margin" => see_pruning_quiet_margin: 0, 1000, 5;
+    "SEE Pruning Capture Margin" => see_pruning_capture_margin: 0, 1000, 10;
+    "Easy Move Depth" => easy_move_depth: 0, u8::MAX as i64, 1;
+    "Easy Move Margin" => easy_move_margin: 0, 1000, 20;
+    //0 is none, 1 is all and 2 is non-losing checks only
+    "Quiescence Checks" => quiescence_checks: 0, 2, 1;
+    "Max Depth" => max_depth: 1, u8::MAX as i64, 1;
+    "Endgame Material" => endgame_material: 0, 10000, 100;
+    "Endgame Max Depth" => endgame_max_depth: 1, u8::MAX as i64, 1;
+    "Handicap Margin" => handicap_margin: 0, 1000, 10;
+    "Random Opening Plies" => random_opening_plies: 0, u8::MAX as i64, 1;
+    "Random Opening Margin" => random_opening_margin: 0, 1000, 10;
+    "Max Principal Variation Length" => max_principal_variation_length: 1, u8::MAX as i64, 1;
+}
+
+///A search feature that can be turned on and off by name at runtime.
+#[derive(Debug, Clone, Copy)]
+pub struct SwitchParam {
+    ///The switch's name, which is also its UCI option name
+    pub name: &'static str,
+    get: fn(&SearchOptions) -> bool,
+    set: fn(&mut SearchOptions, bool)
+}
+
+impl SwitchParam {
+    pub fn get(&self, options: &SearchOptions) -> bool {
+        (self.get)(options)
+    }
+
+    pub fn set(&self, options: &mut SearchOptions, value: bool) {
+        (self.set)(options, value)
+    }
+}
+
+macro_rules! switch_params {
+    ($($name:literal => $field:ident;)*) => {
+        ///Every switch, in a stable order.
+        pub const SWITCH_PARAMS: &[SwitchParam] = &[$(
+            SwitchParam {
+                name: $name,
+                get: |options| options.$field,
+                set: |options, value| options.$field = value
+            }
+        ),*];
+    }
+}
+
+switch_params! {
+    "Null Move Pruning" => null_move_pruning;
+    "Internal Iterative Deepening" => internal_iterative_deepening;
+    "Internal Iterative Reduction" => internal_iterative_reduction;
+    "Passed Pawn Extension" => passed_pawn_extension;
+    "SEE Pruning" => see_pruning;
+    "Quiescence SEE Pruning" => quiescence_see_pruning;
+    "Quiescence Table Reads" => quiescence_table_reads;
+    "Quiescence Table Writes" => quiescence_table_writes;
+    "Endgame Light Eval" => endgame_light_eval;
+}
+
+///Looks up a parameter by name. Names are case insensitive, like UCI option names.
+pub fn find_param(name: &str) -> Option<&'static TunableParam> {
+    TUNABLE_PARAMS.iter().find(|param| param.name.eq_ignore_ascii_case(name))
+}
+
+///Looks up a switch by name. Names are case insensitive, like UCI option names.
+pub fn find_switch(name: &str) -> Option<&'static SwitchParam> {
+    SWITCH_PARAMS.iter().find(|switch| switch.name.eq_ignore_ascii_case(name))
+}
+
+impl SearchOptions {
+    pub fn get_param(&self, name: &str) -> Option<i64> {
+        find_param(name).map(|param| param.get(self))
+    }
+
+    pub fn set_param(&mut self, name: &str, value: i64) -> Result<(), ParamError> {
+        find_param(name)
+            .ok_or_else(|| ParamError::UnknownParam(name.to_owned()))?
+            .set(self, value)
+    }
+
+    pub fn get_switch(&self, name: &str) -> Option<bool> {
+        find_switch(name).map(|switch| switch.get(self))
+    }
+
+    pub fn set_switch(&mut self, name: &str, value: bool) -> Result<(), ParamError> {
+        find_switch(name)
+            .ok_or_else(|| ParamError::UnknownParam(name.to_owned()))?
+            .set(self, value);
+        Ok(())
+    }
+}
@@ -0,0 +1,22 @@
+///Small seedable xorshift64* generator, so randomized play can be reproduced.
+#[derive(Debug, Clone)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        //Xorshift gets stuck on zero, so mix the seed first.
+        Self(seed.wrapping_add(0x9e3779b97f4a7c15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    ///A number in `0..=max`.
+    pub fn up_to(&mut self, max: u16) -> u16 {
+        (self.next_u64() % (max as u64 + 1)) as u16
+    }
+}
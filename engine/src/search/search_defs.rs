@@ -1,7 +1,11 @@
+use std::time::Duration;
+
 use chess::*;
 use serde::{Serialize, Deserialize};
 
-use crate::evaluator::Eval;
+use crate::evaluator::{Eval, SearchEvaluator};
+use crate::table::TtStats;
+use crate::moves::QuiescenceChecks;
 
 pub trait LunaticHandler {
     fn time_up(&mut self) -> bool;
@@ -23,12 +27,49 @@ impl<H: LunaticHandler, R: std::ops::DerefMut<Target=H>> LunaticHandler for R {
 pub struct SearchResult {
     pub mv: ChessMove,
     pub value: Eval,
-    pub nodes: u32,
+    pub nodes: u64,
+    ///Positions resolved by an endgame probe instead of a search.
+    ///The static oracle is the only probe, as there are no tablebases yet.
+    pub tb_hits: u64,
+    ///Time since the search began
+    pub elapsed: Duration,
+    ///Nodes per second over the whole search
+    pub nps: u64,
     pub depth: u8,
     pub sel_depth: u8,
+    ///How many reduced searches had to be repeated at full depth
+    pub re_searches: u32,
     pub principal_variation: Vec<ChessMove>,
+    ///Whether the principal variation was cut off at the maximum length
+    pub principal_variation_truncated: bool,
+    ///The expected reply to the best move. Falls back on the
+    ///transposition table when the principal variation is a single move.
+    pub ponder_move: Option<ChessMove>,
     pub transposition_table_size: usize,
-    pub transposition_table_entries: usize
+    pub transposition_table_entries: usize,
+    ///Estimated permille of the transposition table in use by this search
+    pub hashfull: u32,
+    ///Transposition table statistics for the search so far, for searchers that have one
+    pub transposition_table_stats: Option<TtStats>,
+    ///Whether the best move beat every other root move by at least
+    ///the easy move margin in a shallow scout search.
+    pub easy_move: bool,
+    ///Whether the best move is the only legal root move.
+    ///The search stops after a depth 1 search to get a score for it.
+    pub only_move: bool,
+    ///Approximate memory held by the search's tables and buffers, in bytes
+    pub memory_usage: usize,
+    #[cfg(feature = "hash-verification")]
+    pub transposition_table_collisions: u64
+}
+
+///The outcome of a completed iteration, as kept by the search and its snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IterationResult {
+    pub depth: u8,
+    pub mv: ChessMove,
+    pub value: Eval,
+    pub principal_variation: Vec<ChessMove>
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -47,10 +88,77 @@ pub struct SearchOptions {
     pub late_move_leeway: u8,
     ///Enable null move pruning?
     pub null_move_pruning: bool,
-    ///The number of plies the null move pruning search is reduced by
+    ///The base number of plies the null move pruning search is reduced by
     pub null_move_reduction: u8,
+    ///The null move reduction grows by one ply for every this many plies of depth
+    pub null_move_depth_divisor: u8,
+    ///The null move reduction grows by one ply for every this many centipawns
+    ///the static evaluation is above beta, up to 3 plies
+    pub null_move_eval_margin: i16,
+    ///The minimum depth at which a null move cutoff is verified by a reduced
+    ///search without null moves, to avoid being fooled by zugzwang
+    pub null_move_verification_depth: u8,
+    ///Enable internal iterative deepening at PV nodes without a hash move?
+    pub internal_iterative_deepening: bool,
+    ///The minimum depth for internal iterative deepening and reduction
+    pub iid_min_depth: u8,
+    ///The number of plies internal iterative deepening searches are reduced by
+    pub iid_reduction: u8,
+    ///Reduce expected cut nodes without a hash move by one ply?
+    pub internal_iterative_reduction: bool,
+    ///Extend pushes of passed pawns to the 6th and 7th rank by one ply?
+    ///They're also never reduced by late move reduction.
+    pub passed_pawn_extension: bool,
+    ///Skip moves at low depth whose static exchange evaluation is too negative?
+    pub see_pruning: bool,
+    ///The maximum remaining depth for SEE pruning
+    pub see_pruning_max_depth: u8,
+    ///Quiet moves are pruned if they lose more than this many centipawns times the depth squared
+    pub see_pruning_quiet_margin: i16,
+    ///Captures are pruned if they lose more than this many centipawns times the depth
+    pub see_pruning_capture_margin: i16,
+    ///The depth after which the easy move scout search is run. 0 disables it.
+    pub easy_move_depth: u8,
+    ///How much better (in centipawns) the best move must be than
+    ///every other root move for it to be considered an easy move
+    pub easy_move_margin: i16,
+    ///Skip captures with a negative static exchange evaluation in quiescence search?
+    pub quiescence_see_pruning: bool,
+    ///Which quiet checking moves are searched at the first ply of quiescence search
+    pub quiescence_checks: QuiescenceChecks,
+    ///Use transposition table entries in quiescence search?
+    pub quiescence_table_reads: bool,
+    ///Store quiescence search results in the transposition table?
+    pub quiescence_table_writes: bool,
     pub max_depth: u8,
-    pub max_nodes: u32,
+    ///Positions with at most this much non-pawn material (in centipawns, both sides combined)
+    ///are considered endgames
+    pub endgame_material: i16,
+    ///The max depth used in endgames, if higher than `max_depth`
+    pub endgame_max_depth: u8,
+    ///Skip the evaluation terms that matter little in endgames, to search them deeper
+    pub endgame_light_eval: bool,
+    ///The evaluator used by the search
+    pub evaluator: SearchEvaluator,
+    ///Weakens play by adding up to this many centipawns of random noise
+    ///to each root move's score. 0 disables it.
+    pub handicap_margin: i16,
+    ///Seed for the handicap noise, so handicapped games can be reproduced
+    pub handicap_seed: u64,
+    ///For this many plies from the start of the game, root moves within
+    ///`random_opening_margin` of the best are picked between at random,
+    ///using the same noise and seed as the handicap. 0 disables it.
+    pub random_opening_plies: u8,
+    ///How much worse (in centipawns) than the best move a randomly picked opening move may be
+    pub random_opening_margin: i16,
+    ///Restricts the search to these root moves.
+    ///Illegal moves are ignored, and if none are legal every move is searched.
+    #[serde(skip)]
+    pub root_moves: Option<Vec<ChessMove>>,
+    ///The principal variation is cut off after this many moves
+    pub max_principal_variation_length: u8,
+    ///The search stops after this many nodes once it has a move to play
+    pub max_nodes: u64,
     pub transposition_table_size: usize
 }
 
@@ -61,8 +169,36 @@ impl Default for SearchOptions {
             late_move_leeway: 3,
             null_move_pruning: true,
             null_move_reduction: 2,
+            null_move_depth_divisor: 6,
+            null_move_eval_margin: 200,
+            null_move_verification_depth: 8,
+            internal_iterative_deepening: true,
+            iid_min_depth: 5,
+            iid_reduction: 2,
+            internal_iterative_reduction: true,
+            passed_pawn_extension: true,
+            see_pruning: true,
+            see_pruning_max_depth: 5,
+            see_pruning_quiet_margin: 20,
+            see_pruning_capture_margin: 90,
+            easy_move_depth: 4,
+            easy_move_margin: 200,
+            quiescence_see_pruning: true,
+            quiescence_checks: QuiescenceChecks::None,
+            quiescence_table_reads: true,
+            quiescence_table_writes: true,
             max_depth: 64,
-            max_nodes: u32::MAX,
+            endgame_material: 1000,
+            endgame_max_depth: 100,
+            endgame_light_eval: true,
+            evaluator: SearchEvaluator::default(),
+            handicap_margin: 0,
+            handicap_seed: 0,
+            random_opening_plies: 0,
+            random_opening_margin: 30,
+            root_moves: None,
+            max_principal_variation_length: 64,
+            max_nodes: u64::MAX,
             transposition_table_size: 16_000_000
         }
     }
@@ -0,0 +1,66 @@
+use chess::*;
+
+use super::*;
+
+///Limits for a single search, on top of the handler's time management.
+#[derive(Debug, Copy, Clone)]
+pub struct SearchLimits {
+    ///How deep the search may go. What a ply of depth means is up to the searcher.
+    pub depth: u8,
+    ///How many nodes the search may visit
+    pub nodes: u64
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        Self {
+            depth: u8::MAX,
+            nodes: u64::MAX
+        }
+    }
+}
+
+impl SearchOptions {
+    ///Lowers the max depths and node limit to the limits, which for alpha-beta count plies.
+    ///The max depths are exclusive.
+    pub fn apply_limits(&mut self, limits: SearchLimits) {
+        self.max_depth = self.max_depth.min(limits.depth);
+        self.endgame_max_depth = self.endgame_max_depth.min(limits.depth);
+        self.max_nodes = self.max_nodes.min(limits.nodes);
+    }
+}
+
+///A search algorithm, so different algorithms can be compared behind the same interface.
+///Results are reported to the handler as the search progresses,
+///and the search runs until the handler's time is up or a limit is reached.
+pub trait Searcher {
+    fn search<H: LunaticHandler>(
+        &mut self,
+        handler: H,
+        init_pos: &Board,
+        moves: Vec<ChessMove>,
+        limits: SearchLimits
+    );
+}
+
+///The standard alpha-beta search, run by `LunaticSearchState`.
+///A search state is built around one handler and root position,
+///so it's created anew from these options for each search.
+#[derive(Debug, Clone, Default)]
+pub struct AlphaBetaSearcher {
+    pub options: SearchOptions
+}
+
+impl Searcher for AlphaBetaSearcher {
+    fn search<H: LunaticHandler>(
+        &mut self,
+        handler: H,
+        init_pos: &Board,
+        moves: Vec<ChessMove>,
+        limits: SearchLimits
+    ) {
+        let mut options = self.options.clone();
+        options.apply_limits(limits);
+        LunaticSearchState::new(handler, init_pos, moves, options).search();
+    }
+}
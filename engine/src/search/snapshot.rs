@@ -0,0 +1,115 @@
+use std::io::{self, Read, Write};
+
+use chess::*;
+
+use crate::table::*;
+use super::*;
+
+const MAGIC: &[u8; 4] = b"LNSS";
+const VERSION: u8 = 3;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_move(reader: &mut impl Read) -> io::Result<ChessMove> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    decode_move(u16::from_le_bytes(bytes))?.ok_or_else(|| invalid_data("missing move"))
+}
+
+impl<H: LunaticHandler> LunaticSearchState<H> {
+    ///Writes the root position, the completed iterations,
+    ///the history table and the transposition table.
+    pub fn write_snapshot(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+
+        let fen = self.board.to_string();
+        writer.write_all(&[fen.len() as u8])?;
+        writer.write_all(fen.as_bytes())?;
+        writer.write_all(&(self.game_history.len() as u32).to_le_bytes())?;
+        for hash in &self.game_history {
+            writer.write_all(&hash.to_le_bytes())?;
+        }
+
+        //Depths are below 255, so the count fits a byte
+        writer.write_all(&[self.iterations.len() as u8])?;
+        for iteration in &self.iterations {
+            writer.write_all(&[iteration.depth])?;
+            writer.write_all(&encode_move(Some(iteration.mv)).to_le_bytes())?;
+            writer.write_all(&iteration.value.to_bits().to_le_bytes())?;
+            writer.write_all(&[iteration.principal_variation.len() as u8])?;
+            for &mv in &iteration.principal_variation {
+                writer.write_all(&encode_move(Some(mv)).to_le_bytes())?;
+            }
+        }
+        for &value in self.history_table.iter().flatten().flatten() {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        self.cache_table.write(writer)
+    }
+
+    ///Resumes from a snapshot if it was taken at the same root position.
+    ///The search restarts at the last completed iteration.
+    ///Returns `false` and leaves the state untouched if the root differs.
+    pub fn resume_snapshot(&mut self, reader: &mut impl Read) -> io::Result<bool> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a search snapshot"));
+        }
+        if read_u8(reader)? != VERSION {
+            return Err(invalid_data("unsupported snapshot version"));
+        }
+
+        let mut fen = vec![0; read_u8(reader)? as usize];
+        reader.read_exact(&mut fen)?;
+        let board = std::str::from_utf8(&fen)
+            .ok()
+            .and_then(|fen| fen.parse::<Board>().ok())
+            .ok_or_else(|| invalid_data("invalid board"))?;
+        let mut history = Vec::new();
+        for _ in 0..read_u32(reader)? {
+            history.push(read_u64(reader)?);
+        }
+        if board != self.board || history != self.game_history {
+            return Ok(false);
+        }
+
+        let mut iterations = Vec::new();
+        for _ in 0..read_u8(reader)? {
+            let depth = read_u8(reader)?;
+            let mv = read_move(reader)?;
+            let mut value = [0; 2];
+            reader.read_exact(&mut value)?;
+            let mut principal_variation = Vec::new();
+            for _ in 0..read_u8(reader)? {
+                principal_variation.push(read_move(reader)?);
+            }
+            iterations.push(IterationResult {
+                depth,
+                mv,
+                value: Eval::from_bits(i16::from_le_bytes(value)),
+                principal_variation
+            });
+        }
+        let mut history_table = self.history_table;
+        for value in history_table.iter_mut().flatten().flatten() {
+            *value = read_u32(reader)?;
+        }
+        let cache_table = TranspositionTable::read(reader)?;
+
+        self.start_depth = iterations.last().map_or(0, |iteration| iteration.depth);
+        self.iterations = iterations;
+        self.history_table = history_table;
+        self.cache_table = cache_table;
+        Ok(true)
+    }
+}
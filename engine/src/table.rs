@@ -1,3 +1,8 @@
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
 use chess::*;
 
 use crate::evaluator::*;
@@ -12,32 +17,75 @@ pub enum TableEntryKind {
 #[derive(Debug, Copy, Clone)]
 pub struct TableEntry {
     pub kind: TableEntryKind,
+    ///Mate scores count plies from this entry's position rather than the root
     pub value: Eval,
     ///Remaining depth to max depth (the size of the subtree)
     pub depth: u8,
-    pub best_move: ChessMove
+    ///May be missing for quiescence search entries
+    pub best_move: Option<ChessMove>
+}
+
+///An occupied slot in the table.
+#[derive(Debug, Copy, Clone)]
+struct FullTableEntry {
+    hash: u64,
+    entry: TableEntry,
+    ///The search the entry was last written in
+    generation: u8,
+    ///With hash verification, the full board is kept alongside
+    ///the entry so that hash collisions can be detected.
+    #[cfg(feature = "hash-verification")]
+    board: Board
+}
+
+///Counters for how the table is used, since the last `TranspositionTable::new_search`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct TtStats {
+    pub probes: u64,
+    pub hits: u64,
+    ///Probes that missed while the bucket held other positions.
+    ///These aren't hash collisions, which are only detected with hash verification.
+    pub bucket_misses: u64,
+    ///Stores that replaced an entry for a different position
+    pub overwrites: u64
 }
 
-type FullTableEntry = Option<(u64, TableEntry)>;
+///Entries that share an index. A position can be stored in any of them.
+type Bucket = [Option<FullTableEntry>; BUCKET_SIZE];
+
+const BUCKET_SIZE: usize = 4;
+
+///How many buckets `hashfull` looks at
+const HASHFULL_SAMPLE_BUCKETS: usize = 250;
+
+const FILE_MAGIC: &[u8; 4] = b"LNTT";
+const FILE_VERSION: u8 = 1;
 
 #[derive(Debug)]
 pub struct TranspositionTable {
-    table: Box<[FullTableEntry]>,
+    table: Box<[Bucket]>,
     len: usize,
-    mask: usize
+    mask: usize,
+    generation: u8,
+    stats: Cell<TtStats>,
+    #[cfg(feature = "hash-verification")]
+    collisions: Cell<u64>
 }
 
-//TODO consider using `unsafe` to speed up transposition table access by removing bounds checking?
 impl TranspositionTable {
-    ///Rounds up the number of entries to a power of two.
+    ///Rounds up the number of entries to a power of two, and to at least one bucket.
     ///`panic` on overflow.
     pub fn with_rounded_entries(entries: usize) -> Self {
-        let entries = entries.checked_next_power_of_two().unwrap();
-        let table = vec![None; entries].into_boxed_slice();
+        let entries = entries.max(BUCKET_SIZE).checked_next_power_of_two().unwrap();
+        let table = vec![[None; BUCKET_SIZE]; entries / BUCKET_SIZE].into_boxed_slice();
         Self {
             len: 0,
             mask: table.len() - 1,
-            table
+            table,
+            generation: 0,
+            stats: Cell::new(TtStats::default()),
+            #[cfg(feature = "hash-verification")]
+            collisions: Cell::new(0)
         }
     }
 
@@ -45,17 +93,76 @@ impl TranspositionTable {
     ///then rounds up the size to the nearest power of two.
     ///`panic` on overflow.
     pub fn with_rounded_size(size: usize) -> Self {
-        Self::with_rounded_entries(size / std::mem::size_of::<FullTableEntry>())
+        Self::with_rounded_entries(size / Self::ENTRY_SIZE)
+    }
+
+    ///The size of a single entry in bytes.
+    pub const ENTRY_SIZE: usize = std::mem::size_of::<Option<FullTableEntry>>();
+
+    ///The largest table size in bytes accepted from a file.
+    pub const MAX_SIZE: u64 = 64_000_000_000;
+
+    ///The number of entries `with_rounded_size` allocates for `size` bytes,
+    ///without allocating the table.
+    pub fn rounded_entries(size: usize) -> usize {
+        (size / Self::ENTRY_SIZE).max(BUCKET_SIZE).checked_next_power_of_two().unwrap()
+    }
+
+    ///Starts a new search, aging every entry written so far and resetting the statistics.
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        self.stats.set(TtStats::default());
+    }
+
+    pub fn stats(&self) -> TtStats {
+        self.stats.get()
+    }
+
+    fn update_stats(&self, update: impl FnOnce(&mut TtStats)) {
+        let mut stats = self.stats.get();
+        update(&mut stats);
+        self.stats.set(stats);
+    }
+
+    fn bucket(&self, hash: u64) -> &Bucket {
+        let index = hash as usize & self.mask;
+        #[cfg(feature = "unchecked-tt")]
+        //SAFETY: The table's length is a power of two and never changes,
+        //and `mask` is one less than it, so the index is always in bounds.
+        return unsafe { self.table.get_unchecked(index) };
+        #[cfg(not(feature = "unchecked-tt"))]
+        &self.table[index]
     }
 
+    fn bucket_mut(&mut self, hash: u64) -> &mut Bucket {
+        let index = hash as usize & self.mask;
+        #[cfg(feature = "unchecked-tt")]
+        //SAFETY: See `bucket`.
+        return unsafe { self.table.get_unchecked_mut(index) };
+        #[cfg(not(feature = "unchecked-tt"))]
+        &mut self.table[index]
+    }
+
+    ///Collisions are counted and treated as a miss with hash verification.
     pub fn get(&self, board: &Board) -> Option<TableEntry> {
         let hash = board.get_hash();
-        if let Some((entry_hash, entry)) = self.table[hash as usize & self.mask] {
-            if entry_hash == hash {
-                return Some(entry);
+        let bucket = self.bucket(hash);
+        let slot = bucket.iter().flatten().find(|slot| slot.hash == hash);
+        self.update_stats(|stats| {
+            stats.probes += 1;
+            if slot.is_some() {
+                stats.hits += 1;
+            } else if bucket.iter().any(Option::is_some) {
+                stats.bucket_misses += 1;
             }
+        });
+        let slot = slot?;
+        #[cfg(feature = "hash-verification")]
+        if slot.board != *board {
+            self.collisions.set(self.collisions.get() + 1);
+            return None;
         }
-        None
+        Some(slot.entry)
     }
 
     pub fn set(
@@ -63,26 +170,282 @@ impl TranspositionTable {
         board: &Board,
         entry: TableEntry
     ) {
-        let hash = board.get_hash();
-        let old = &mut self.table[hash as usize & self.mask];
-        if let Some(old) = old {
-            if old.0 == hash || entry.depth > old.1.depth {
-                //Matching hashes uses the newer entry since it has more information.
-                //Otherwise, select the deeper entry.
-                *old = (hash, entry);
+        self.insert(FullTableEntry {
+            hash: board.get_hash(),
+            entry,
+            generation: self.generation,
+            #[cfg(feature = "hash-verification")]
+            board: *board
+        });
+    }
+
+    ///How worthwhile an entry is to keep in the depth preferred slot.
+    ///Deeper entries save more work, exact entries are more useful than bounds
+    ///and entries from earlier searches are less likely to be reached again.
+    fn priority(&self, slot: &FullTableEntry) -> i32 {
+        let exact = (slot.entry.kind == TableEntryKind::Exact) as i32;
+        slot.entry.depth as i32 * 2 + exact - self.age(slot) as i32 * 8
+    }
+
+    ///How many searches ago the entry was written.
+    fn age(&self, slot: &FullTableEntry) -> u8 {
+        self.generation.wrapping_sub(slot.generation)
+    }
+
+    ///The first slot of each bucket prefers valuable entries. An entry it displaces
+    ///moves to the other slots, which always take new entries in place of the oldest one,
+    ///so deep entries are kept without starving the table of recent shallow ones.
+    fn insert(&mut self, mut new: FullTableEntry) {
+        let bucket = self.bucket(new.hash);
+        if let Some(i) = bucket.iter().position(|slot| matches!(slot, Some(slot) if slot.hash == new.hash)) {
+            let old = bucket[i].unwrap();
+            if new.entry.best_move.is_none() {
+                //Quiescence entries may not have a move, so keep the old one.
+                new.entry.best_move = old.entry.best_move;
+            }
+            //Quiescence search entries have a depth of 0. They never
+            //replace entries from the main search, even for the same position.
+            //Otherwise the newer entry has more information.
+            if new.entry.depth > 0 || old.entry.depth == 0 {
+                self.bucket_mut(new.hash)[i] = Some(new);
+            } else {
+                //Still refresh its age, since the position was reached again.
+                self.bucket_mut(new.hash)[i].as_mut().unwrap().generation = new.generation;
             }
-        } else {
-            //Insert to empty slot
-            self.len += 1;
-            *old = Some((hash, entry));
+            return;
         }
+
+        let depth_preferred = bucket[0].is_none_or(|old| self.priority(&new) >= self.priority(&old));
+        if depth_preferred {
+            match self.bucket_mut(new.hash)[0].replace(new) {
+                Some(old) => new = old,
+                None => {
+                    self.len += 1;
+                    return;
+                }
+            }
+        }
+        let bucket = self.bucket(new.hash);
+        let i = match bucket[1..].iter().position(Option::is_none) {
+            Some(i) => {
+                //Insert to empty slot
+                self.len += 1;
+                i
+            }
+            None => {
+                self.update_stats(|stats| stats.overwrites += 1);
+                bucket[1..]
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|(_, slot)| {
+                        let slot = slot.as_ref().unwrap();
+                        (self.age(slot), std::cmp::Reverse(slot.entry.depth))
+                    })
+                    .unwrap()
+                    .0
+            }
+        };
+        self.bucket_mut(new.hash)[i + 1] = Some(new);
     }
 
+    ///The number of entries.
     pub fn capacity(&self) -> usize {
-        self.table.len()
+        self.table.len() * BUCKET_SIZE
     }
 
     pub fn len(&self) -> usize {
         self.len
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    ///Estimates how full the table is in permille from the first buckets,
+    ///counting only entries written by the current search.
+    pub fn hashfull(&self) -> u32 {
+        let sample = &self.table[..self.table.len().min(HASHFULL_SAMPLE_BUCKETS)];
+        let used = sample
+            .iter()
+            .flatten()
+            .flatten()
+            .filter(|slot| slot.generation == self.generation)
+            .count();
+        (used * 1000 / (sample.len() * BUCKET_SIZE)) as u32
+    }
+
+    ///Removes every entry, keeping the allocation.
+    pub fn clear(&mut self) {
+        self.table.fill([None; BUCKET_SIZE]);
+        self.len = 0;
+    }
+
+    ///The size of the table in bytes.
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of_val(&*self.table)
+    }
+
+    ///The number of hash collisions detected so far.
+    #[cfg(feature = "hash-verification")]
+    pub fn collisions(&self) -> u64 {
+        self.collisions.get()
+    }
+
+    ///Moves every entry to a table with the given number of entries, rounded like `with_rounded_entries`.
+    ///Entries are dropped if the new table is too small to hold them all.
+    pub fn resized(self, entries: usize) -> Self {
+        let mut table = Self::with_rounded_entries(entries);
+        table.generation = self.generation;
+        for slot in self.table.iter().flatten().flatten() {
+            table.insert(*slot);
+        }
+        table
+    }
+
+    ///Saves the table to a file, so later sessions can start with what this one learned.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(FILE_MAGIC)?;
+        writer.write_all(&[FILE_VERSION])?;
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    ///Loads a table saved by `save`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != FILE_MAGIC {
+            return Err(invalid_data("not a transposition table file"));
+        }
+        if read_u8(&mut reader)? != FILE_VERSION {
+            return Err(invalid_data("unsupported transposition table file version"));
+        }
+        Self::read(&mut reader)
+    }
+
+    ///Writes the capacity followed by every occupied entry.
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&(self.capacity() as u64).to_le_bytes())?;
+        writer.write_all(&(self.len as u64).to_le_bytes())?;
+        for slot in self.table.iter().flatten().flatten() {
+            write_entry(writer, slot.hash, &slot.entry)?;
+            #[cfg(feature = "hash-verification")]
+            {
+                let fen = slot.board.to_string();
+                writer.write_all(&[fen.len() as u8])?;
+                writer.write_all(fen.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    ///Reads a table written by `write`.
+    pub fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let capacity = read_u64(reader)?;
+        if !capacity.is_power_of_two() {
+            return Err(invalid_data("table capacity is not a power of two"));
+        }
+        //Checked before allocating, so a corrupt file can't request an arbitrarily large table
+        if capacity.saturating_mul(Self::ENTRY_SIZE as u64) > Self::MAX_SIZE {
+            return Err(invalid_data("table capacity is too large"));
+        }
+        let len = read_u64(reader)?;
+        if len > capacity {
+            return Err(invalid_data("table has more entries than its capacity"));
+        }
+        let mut table = Self::with_rounded_entries(capacity as usize);
+        for _ in 0..len {
+            let (hash, entry) = read_entry(reader)?;
+            #[cfg(feature = "hash-verification")]
+            let board = {
+                let mut fen = vec![0; read_u8(reader)? as usize];
+                reader.read_exact(&mut fen)?;
+                std::str::from_utf8(&fen)
+                    .ok()
+                    .and_then(|fen| fen.parse::<Board>().ok())
+                    .ok_or_else(|| invalid_data("invalid board"))?
+            };
+            table.insert(FullTableEntry {
+                hash,
+                entry,
+                generation: table.generation,
+                #[cfg(feature = "hash-verification")]
+                board
+            });
+        }
+        Ok(table)
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub(crate) fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+pub(crate) fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+const NO_MOVE: u16 = u16::MAX;
+
+//Source, destination and promotion (plus one) packed into 15 bits.
+pub(crate) fn encode_move(mv: Option<ChessMove>) -> u16 {
+    mv.map_or(NO_MOVE, |mv| {
+        let promotion = mv.get_promotion().map_or(0, |p| p.to_index() as u16 + 1);
+        mv.get_source().to_index() as u16
+            | (mv.get_dest().to_index() as u16) << 6
+            | promotion << 12
+    })
+}
+
+pub(crate) fn decode_move(bits: u16) -> io::Result<Option<ChessMove>> {
+    if bits == NO_MOVE {
+        return Ok(None);
+    }
+    let square = |index: u16| ALL_SQUARES[(index & 0b111111) as usize];
+    let promotion = match bits >> 12 {
+        0 => None,
+        p if p as usize <= NUM_PIECES => Some(ALL_PIECES[p as usize - 1]),
+        _ => return Err(invalid_data("invalid promotion"))
+    };
+    Ok(Some(ChessMove::new(square(bits), square(bits >> 6), promotion)))
+}
+
+fn write_entry(writer: &mut impl Write, hash: u64, entry: &TableEntry) -> io::Result<()> {
+    let kind = match entry.kind {
+        TableEntryKind::Exact => 0u8,
+        TableEntryKind::LowerBound => 1,
+        TableEntryKind::UpperBound => 2
+    };
+    writer.write_all(&hash.to_le_bytes())?;
+    writer.write_all(&[kind, entry.depth])?;
+    writer.write_all(&entry.value.to_bits().to_le_bytes())?;
+    writer.write_all(&encode_move(entry.best_move).to_le_bytes())
+}
+
+fn read_entry(reader: &mut impl Read) -> io::Result<(u64, TableEntry)> {
+    let hash = read_u64(reader)?;
+    let mut bytes = [0; 6];
+    reader.read_exact(&mut bytes)?;
+    let kind = match bytes[0] {
+        0 => TableEntryKind::Exact,
+        1 => TableEntryKind::LowerBound,
+        2 => TableEntryKind::UpperBound,
+        _ => return Err(invalid_data("invalid entry kind"))
+    };
+    Ok((hash, TableEntry {
+        kind,
+        value: Eval::from_bits(i16::from_le_bytes([bytes[2], bytes[3]])),
+        depth: bytes[1],
+        best_move: decode_move(u16::from_le_bytes([bytes[4], bytes[5]]))?
+    }))
 }
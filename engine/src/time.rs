@@ -1,14 +1,69 @@
 use std::time::Duration;
 
+use chess::*;
+
 use crate::search::SearchResult;
 use crate::evaluator::*;
 
+///The state of the clock at the start of a search, as sent by the GUI.
+#[derive(Debug, Clone)]
+pub struct ClockInfo {
+    pub white_time: Option<Duration>,
+    pub black_time: Option<Duration>,
+    pub white_increment: Option<Duration>,
+    pub black_increment: Option<Duration>,
+    ///The number of moves until the next time control, if any
+    pub moves_to_go: Option<u8>,
+    pub side_to_move: Color,
+    ///The full move number, starting at 1
+    pub move_number: u32
+}
+
+impl ClockInfo {
+    ///A clock without any time controls.
+    pub fn untimed(side_to_move: Color, move_number: u32) -> Self {
+        Self {
+            white_time: None,
+            black_time: None,
+            white_increment: None,
+            black_increment: None,
+            moves_to_go: None,
+            side_to_move,
+            move_number
+        }
+    }
+
+    ///The time left for the side to move, if it's on the clock.
+    pub fn time_left(&self) -> Option<Duration> {
+        match self.side_to_move {
+            Color::White => self.white_time,
+            Color::Black => self.black_time
+        }
+    }
+
+    ///The increment for the side to move.
+    pub fn increment(&self) -> Duration {
+        match self.side_to_move {
+            Color::White => self.white_increment,
+            Color::Black => self.black_increment
+        }.unwrap_or(Duration::ZERO)
+    }
+}
+
 pub trait TimeManager {
+    ///Start managing a new search with the state of the clock.
+    fn start(&mut self, _clock: &ClockInfo) {}
+
     ///Update the time manager's internal state with a new result.
     ///`time` represents the duration since the last update.
     ///Returns a timeout to the next update; If no update happens before
     ///the timeout, stop searching.
     fn update(&mut self, result: SearchResult, time: Duration) -> Duration;
+
+    ///A cap on the search depth, if the time manager wants one.
+    fn max_depth(&self) -> Option<u8> {
+        None
+    }
 }
 
 ///Extremely naive time manager that only uses a fixed amount of time per move.
@@ -27,6 +82,10 @@ impl FixedTimeManager {
 }
 
 impl TimeManager for FixedTimeManager {
+    fn start(&mut self, _: &ClockInfo) {
+        self.elapsed = Duration::ZERO;
+    }
+
     fn update(&mut self, _: SearchResult, time: Duration) -> Duration {
         self.elapsed += time;
         if self.interval > self.elapsed {
@@ -37,37 +96,230 @@ impl TimeManager for FixedTimeManager {
     }
 }
 
-///Extremely naive time manager that only uses a fixed percentage of time per move
-pub struct PercentageTimeManager(FixedTimeManager);
+///Extremely naive time manager that only uses a fixed percentage of time per move.
+///Searches without a clock are infinite.
+pub struct PercentageTimeManager {
+    inner: FixedTimeManager,
+    percentage: f32,
+    minimum_time: Duration
+}
 
 impl PercentageTimeManager {
-    pub fn new(time_left: Duration, percentage: f32, minimum_time: Duration) -> Self {
-        Self(FixedTimeManager::new(time_left.mul_f32(percentage).max(minimum_time)))
+    pub fn new(percentage: f32, minimum_time: Duration) -> Self {
+        Self {
+            inner: FixedTimeManager::new(Duration::MAX),
+            percentage,
+            minimum_time
+        }
     }
 }
 
 impl TimeManager for PercentageTimeManager {
+    fn start(&mut self, clock: &ClockInfo) {
+        let interval = match clock.time_left() {
+            Some(time_left) => time_left.mul_f32(self.percentage).max(self.minimum_time),
+            None => Duration::MAX
+        };
+        self.inner = FixedTimeManager::new(interval);
+    }
+
     fn update(&mut self, result: SearchResult, time: Duration) -> Duration {
-        self.0.update(result, time)
+        self.inner.update(result, time)
+    }
+}
+
+///Stops after a fixed number of nodes instead of wall time, for deterministic play and testing.
+///Only checked between iterations; `SearchOptions::max_nodes` also stops a search mid-iteration.
+pub struct NodeCountTimeManager {
+    max_nodes: u64
+}
+
+impl NodeCountTimeManager {
+    pub fn new(max_nodes: u64) -> Self {
+        Self {
+            max_nodes
+        }
+    }
+}
+
+impl TimeManager for NodeCountTimeManager {
+    fn update(&mut self, result: SearchResult, _: Duration) -> Duration {
+        if result.only_move || result.nodes >= self.max_nodes {
+            Duration::ZERO
+        } else {
+            Duration::MAX
+        }
     }
 }
 
 ///The standard time manager. Still quite naive.
-pub struct StandardTimeManager(PercentageTimeManager);
+///Thinks less when the best move is stable across iterations and more when it keeps changing.
+///If the score drops sharply, the soft allocation is extended up to the hard panic allocation.
+///When the clock is nearly out, it switches to an emergency mode that never extends the allocation.
+///A new iteration isn't started if it likely won't finish in the remaining time.
+///Searches without a clock are infinite.
+pub struct StandardTimeManager {
+    percentage: f32,
+    minimum_time: Duration,
+    panic_percentage: f32,
+    emergency_time: Duration,
+    inner: FixedTimeManager,
+    emergency: bool,
+    panic_interval: Duration,
+    prev_value: Option<Eval>,
+    best_move: Option<ChessMove>,
+    ///Iterations in a row that kept the same best move
+    stable_iterations: u32,
+    ///Recent best move changes. Decays by one for every iteration without a change.
+    instability: u32,
+    ///Total nodes at the last update
+    prev_nodes: u64,
+    ///Nodes used by the last iteration
+    prev_iteration_nodes: Option<u64>
+}
 
 impl StandardTimeManager {
-    pub fn new(time_left: Duration, percentage: f32, minimum_time: Duration) -> Self {
-        Self(PercentageTimeManager::new(time_left, percentage, minimum_time))
+    ///Easy moves only use this fraction of the allocated time.
+    const EASY_MOVE_DIVISOR: u32 = 4;
+    ///After this many iterations with the same best move, only half the allocated time is used.
+    const STABLE_ITERATIONS: u32 = 4;
+    ///With at least this much instability, the allocated time is extended by half.
+    const UNSTABLE_CHANGES: u32 = 2;
+    ///A score drop of at least this many centipawns since the last iteration triggers panic time.
+    const PANIC_SCORE_DROP: i16 = 50;
+    ///In emergency mode, at most this fraction of the clock is used per move.
+    const EMERGENCY_PERCENTAGE: f32 = 0.02;
+    ///In emergency mode, the search depth is capped to this.
+    const EMERGENCY_MAX_DEPTH: u8 = 8;
+    ///Bounds for the effective branching factor used to predict the next iteration's time.
+    const MIN_BRANCHING_FACTOR: f32 = 1.0;
+    const MAX_BRANCHING_FACTOR: f32 = 8.0;
+    ///The fraction of the increment added to each move's allocation.
+    const INCREMENT_USAGE: f32 = 0.75;
+
+    ///Uses `percentage` of the clock per move, but at least `minimum_time`.
+    ///With moves to go, the clock is split evenly across them instead.
+    ///`panic_percentage` is the hard cap used when the score drops, which is never below the normal allocation.
+    ///Emergency mode kicks in with less than `emergency_time` left.
+    pub fn new(percentage: f32, minimum_time: Duration, panic_percentage: f32, emergency_time: Duration) -> Self {
+        Self {
+            percentage,
+            minimum_time,
+            panic_percentage,
+            emergency_time,
+            inner: FixedTimeManager::new(Duration::MAX),
+            emergency: false,
+            panic_interval: Duration::MAX,
+            prev_value: None,
+            best_move: None,
+            stable_iterations: 0,
+            instability: 0,
+            prev_nodes: 0,
+            prev_iteration_nodes: None
+        }
+    }
+
+    ///Predicts how long the next iteration will take from the effective branching factor
+    ///of the last two iterations. `time` is the duration of the last iteration.
+    fn predict_iteration(&mut self, nodes: u64, time: Duration) -> Option<Duration> {
+        let iteration_nodes = nodes - self.prev_nodes;
+        self.prev_nodes = nodes;
+        let prev_iteration_nodes = self.prev_iteration_nodes.replace(iteration_nodes)?;
+        let branching_factor = (iteration_nodes as f32 / prev_iteration_nodes.max(1) as f32)
+            .clamp(Self::MIN_BRANCHING_FACTOR, Self::MAX_BRANCHING_FACTOR);
+        Some(time.mul_f32(branching_factor))
+    }
+
+    fn update_stability(&mut self, mv: ChessMove) {
+        match self.best_move {
+            Some(best_move) if best_move == mv => {
+                self.stable_iterations += 1;
+                self.instability = self.instability.saturating_sub(1);
+            }
+            Some(_) => {
+                self.stable_iterations = 0;
+                self.instability += 1;
+            }
+            None => {}
+        }
+        self.best_move = Some(mv);
     }
 }
 
 impl TimeManager for StandardTimeManager {
+    fn start(&mut self, clock: &ClockInfo) {
+        *self = Self::new(self.percentage, self.minimum_time, self.panic_percentage, self.emergency_time);
+        let time_left = match clock.time_left() {
+            Some(time_left) => time_left,
+            None => return
+        };
+        let mut percentage = match clock.moves_to_go {
+            //One extra move of slack so the last move before the time control isn't played on an empty clock
+            Some(moves_to_go) => 1.0 / (moves_to_go as f32 + 1.0),
+            None => self.percentage
+        };
+        let mut minimum_time = self.minimum_time;
+        let mut panic_percentage = self.panic_percentage;
+        self.emergency = time_left < self.emergency_time;
+        if self.emergency {
+            percentage = percentage.min(Self::EMERGENCY_PERCENTAGE);
+            minimum_time = Duration::ZERO;
+            panic_percentage = 0.0;
+        }
+        //The increment comes back after the move, but never plan on more than half the clock
+        let max_interval = time_left / 2;
+        let interval = (time_left.mul_f32(percentage) + clock.increment().mul_f32(Self::INCREMENT_USAGE))
+            .max(minimum_time)
+            .min(max_interval);
+        self.panic_interval = time_left.mul_f32(panic_percentage).min(max_interval).max(interval);
+        self.inner = FixedTimeManager::new(interval);
+    }
+
     fn update(&mut self, result: SearchResult, time: Duration) -> Duration {
-        if let EvalKind::Centipawn(_) = result.value.kind() {
-            self.0.update(result, time)
+        if result.only_move {
+            //Nothing to think about
+            return Duration::ZERO;
+        }
+        self.update_stability(result.mv);
+        let predicted_iteration = self.predict_iteration(result.nodes, time);
+        let prev_value = self.prev_value.replace(result.value);
+        if let EvalKind::Centipawn(cp) = result.value.kind() {
+            let panic = match prev_value.map(Eval::kind) {
+                Some(EvalKind::Centipawn(prev_cp)) => prev_cp.saturating_sub(cp) >= Self::PANIC_SCORE_DROP,
+                _ => false
+            };
+            let easy_move = result.easy_move;
+            self.inner.update(result, time);
+            let inner = &self.inner;
+            let interval = if panic {
+                self.panic_interval
+            } else if easy_move {
+                inner.interval / Self::EASY_MOVE_DIVISOR
+            } else if self.stable_iterations >= Self::STABLE_ITERATIONS {
+                inner.interval / 2
+            } else if self.instability >= Self::UNSTABLE_CHANGES && !self.emergency {
+                //Saturating, since infinite searches have an interval of Duration::MAX
+                inner.interval.saturating_add(inner.interval / 2)
+            } else {
+                inner.interval
+            };
+            let timeout = interval.saturating_sub(inner.elapsed);
+            match predicted_iteration {
+                //Finish now instead of starting an iteration that would likely be aborted
+                Some(predicted_iteration) if predicted_iteration > timeout => Duration::ZERO,
+                _ => timeout
+            }
         } else {
             //Forced outcome, cut thinking short
             Duration::ZERO
         }
     }
+
+    fn max_depth(&self) -> Option<u8> {
+        if self.emergency {
+            Some(Self::EMERGENCY_MAX_DEPTH)
+        } else {
+            None
+        }
+    }
 }
@@ -0,0 +1,109 @@
+use std::collections::VecDeque;
+
+use crate::evaluator::*;
+
+///Mate scores are clamped to this many centipawns so they don't swamp the statistics.
+const MATE_CENTIPAWNS: i16 = 10_000;
+
+///Slopes (in centipawns per move) smaller than this count as flat.
+const FLAT_SLOPE: f32 = 5.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TrendDirection {
+    Rising,
+    Falling,
+    Flat
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct TrendSummary {
+    ///Mean score in centipawns
+    pub mean: f32,
+    ///Variance of the scores in centipawns squared
+    pub variance: f32,
+    ///Least squares slope of the scores in centipawns per move
+    pub slope: f32,
+    pub direction: TrendDirection
+}
+
+///A rolling window of the final scores of our last few moves in a game,
+///so decisions like draw offers don't hinge on a single move's score.
+#[derive(Debug, Clone)]
+pub struct ScoreTrend {
+    window: usize,
+    scores: VecDeque<i16>
+}
+
+impl ScoreTrend {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window,
+            scores: VecDeque::with_capacity(window)
+        }
+    }
+
+    ///Records the final score of one of our moves, from our perspective.
+    pub fn push(&mut self, value: Eval) {
+        let score = match value.kind() {
+            EvalKind::Centipawn(cp) => cp.clamp(-MATE_CENTIPAWNS, MATE_CENTIPAWNS),
+            EvalKind::MateIn(_) => MATE_CENTIPAWNS,
+            EvalKind::MatedIn(_) => -MATE_CENTIPAWNS
+        };
+        if self.scores.len() == self.window {
+            self.scores.pop_front();
+        }
+        if self.window > 0 {
+            self.scores.push_back(score);
+        }
+    }
+
+    ///Forget every score, such as at the start of a new game.
+    pub fn clear(&mut self) {
+        self.scores.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    ///`None` until at least two scores are recorded.
+    pub fn summary(&self) -> Option<TrendSummary> {
+        if self.scores.len() < 2 {
+            return None;
+        }
+        let count = self.scores.len() as f32;
+        let mean = self.scores.iter().map(|&s| s as f32).sum::<f32>() / count;
+        let variance = self.scores
+            .iter()
+            .map(|&s| (s as f32 - mean).powi(2))
+            .sum::<f32>() / count;
+
+        let mean_index = (count - 1.0) / 2.0;
+        let mut covariance = 0.0;
+        let mut index_variance = 0.0;
+        for (index, &score) in self.scores.iter().enumerate() {
+            let index = index as f32 - mean_index;
+            covariance += index * (score as f32 - mean);
+            index_variance += index * index;
+        }
+        let slope = covariance / index_variance;
+        let direction = if slope >= FLAT_SLOPE {
+            TrendDirection::Rising
+        } else if slope <= -FLAT_SLOPE {
+            TrendDirection::Falling
+        } else {
+            TrendDirection::Flat
+        };
+
+        Some(TrendSummary {
+            mean,
+            variance,
+            slope,
+            direction
+        })
+    }
+}
@@ -0,0 +1,377 @@
+use chess::*;
+
+use lunatic::evaluator::*;
+
+//Positions with castling, en passant and promotions available
+const POSITIONS: &[&str] = &[
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+    "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+    //Opposite colored bishops
+    "8/5k2/3b4/2p5/2P1P3/3B4/4K3/8 w - - 0 1"
+];
+
+#[test]
+fn incremental_state_matches_full_state() {
+    let evaluator = StandardEvaluator::default();
+    let mut seed = 0x2545F4914F6CDD1Du64;
+    for fen in POSITIONS {
+        for _ in 0..20 {
+            let mut board: Board = fen.parse().unwrap();
+            let mut state = evaluator.eval_state(&board);
+            for _ in 0..40 {
+                let moves = MoveGen::new_legal(&board).collect::<Vec<_>>();
+                if moves.is_empty() {
+                    break;
+                }
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                let mv = moves[(seed % moves.len() as u64) as usize];
+                state = evaluator.update_eval_state(&state, &board, mv);
+                board = board.make_move_new(mv);
+                assert_eq!(state, evaluator.eval_state(&board), "{} after {}", board, mv);
+                assert_eq!(evaluator.evaluate_with_state(&board, &state), evaluator.evaluate(&board));
+            }
+        }
+    }
+}
+
+#[test]
+fn trace_adds_up_to_evaluation() {
+    let evaluator = StandardEvaluator::default();
+    for fen in POSITIONS {
+        let board: Board = fen.parse().unwrap();
+        let trace = evaluator.evaluate_verbose(&board);
+        assert_eq!(trace.value, evaluator.evaluate(&board));
+        //Each side's terms are interpolated together, so only check the sums before interpolation.
+        let side_value = |side: Color| {
+            let midgame = trace.terms.iter().map(|term| term.values[side.to_index()].midgame).sum();
+            let endgame = trace.terms.iter().map(|term| term.values[side.to_index()].endgame).sum();
+            TaperedValue { midgame, endgame }.interpolate(trace.phase)
+        };
+        let us = side_value(board.side_to_move());
+        let them = side_value(!board.side_to_move());
+        let scale = match trace.adjustment {
+            EndgameEval::Scale(scale) => scale,
+            EndgameEval::Exact(_) => panic!("Unexpected exact endgame in {}", fen)
+        };
+        let scaled = (us - them) as i32 * scale as i32 / DrawishScaling::FULL as i32;
+        assert_eq!(trace.value, Eval::cp(scaled as i16));
+    }
+}
+
+fn scale(fen: &str) -> i16 {
+    match StandardEvaluator::default().evaluate_verbose(&fen.parse().unwrap()).adjustment {
+        EndgameEval::Scale(scale) => scale,
+        EndgameEval::Exact(_) => panic!("Unexpected exact endgame in {}", fen)
+    }
+}
+
+#[test]
+fn drawish_endgames_are_scaled() {
+    let scaling = DrawishScaling::default();
+    assert_eq!(scale("8/5k2/3b4/2p5/2P1P3/3B4/4K3/8 w - - 0 1"), scaling.opposite_bishops);
+    //Light squared bishop can't drive the king from h8
+    assert_eq!(scale("7k/8/6KP/8/8/8/8/5B2 w - - 0 1"), scaling.wrong_bishop);
+    assert_eq!(scale("7k/8/6KP/8/8/8/8/8 w - - 0 1"), scaling.wrong_bishop);
+    //KRvKB
+    assert_eq!(scale("8/8/3k4/8/8/2b5/8/2R1K3 w - - 0 1"), scaling.no_pawns);
+    //KNNvK
+    assert_eq!(scale("8/8/3k4/8/8/8/8/1NN1K3 w - - 0 1"), scaling.no_pawns);
+
+    //Right bishop
+    assert_eq!(scale("7k/8/6KP/8/8/8/8/4B3 w - - 0 1"), DrawishScaling::FULL);
+    //Same colored bishops
+    assert_eq!(scale("8/5k2/4b3/2p5/2P1P3/3B4/4K3/8 w - - 0 1"), DrawishScaling::FULL);
+    //Opposite colored bishops, but three pawns up
+    assert_eq!(scale("8/5k2/3b4/8/1PP1PP2/3B4/4K3/8 w - - 0 1"), DrawishScaling::FULL);
+    //Opposite colored bishops with no pawns is a draw anyway
+    assert_eq!(scale("8/5k2/3b4/8/8/3B4/4K3/8 w - - 0 1"), scaling.no_pawns);
+    assert_eq!(scale("8/8/3k4/8/8/8/8/2R1K3 w - - 0 1"), DrawishScaling::FULL);
+}
+
+fn endgame(fen: &str) -> (Option<&'static str>, EndgameEval) {
+    let trace = StandardEvaluator::default().evaluate_verbose(&fen.parse().unwrap());
+    (trace.endgame, trace.adjustment)
+}
+
+#[test]
+fn specialized_endgames() {
+    let scaling = DrawishScaling::default();
+    let full = EndgameEval::Scale(DrawishScaling::FULL);
+    //Rook pawn
+    assert_eq!(endgame("4k3/8/8/8/P7/3K4/8/1R3r2 w - - 0 1"), (Some("KRPvKR"), EndgameEval::Scale(scaling.rook_and_pawn)));
+    //Defending king in front of the pawn, with colors reversed
+    assert_eq!(endgame("7R/8/3r4/8/3p4/8/3K4/k7 b - - 0 1").1, EndgameEval::Scale(scaling.rook_and_pawn));
+    assert_eq!(endgame("4k3/8/8/8/3P4/3K4/8/1R3r2 w - - 0 1"), (Some("KRPvKR"), full));
+
+    //Supported rook pawn on the seventh with the queen's king far away
+    assert_eq!(endgame("7K/8/8/8/6Q1/8/p7/1k6 w - - 0 1"), (Some("KQvKP"), EndgameEval::Scale(scaling.queen_vs_pawn)));
+    //Center pawns lose
+    assert_eq!(endgame("7K/8/8/8/6Q1/8/3p4/2k5 w - - 0 1"), (Some("KQvKP"), full));
+
+    assert_eq!(endgame("8/8/3k4/8/8/8/8/2R1K3 w - - 0 1").0, None);
+}
+
+#[test]
+fn load_from_path() {
+    let path = std::env::temp_dir().join(format!("lunatic-eval-{}.bin", std::process::id()));
+    let mut evaluator = StandardEvaluator::default();
+    evaluator.piece_values.knight = 300;
+    evaluator.write_binary(&mut std::fs::File::create(&path).unwrap()).unwrap();
+    let loaded = StandardEvaluator::from_path(&path).unwrap();
+    assert_eq!(loaded.checksum(), evaluator.checksum());
+
+    evaluator.drawish_scaling.no_pawns = DrawishScaling::FULL + 1;
+    evaluator.write_binary(&mut std::fs::File::create(&path).unwrap()).unwrap();
+    let error = StandardEvaluator::from_path(&path).unwrap_err();
+    assert!(matches!(error, EvalFileError::InvalidValue { .. }), "{}", error);
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(matches!(StandardEvaluator::from_path(&path), Err(EvalFileError::Io(_))));
+}
+
+//Every weight at the limit `validate` allows, all pushing in the same direction.
+fn extreme_evaluator(sign: i16) -> StandardEvaluator {
+    let mut evaluator = StandardEvaluator::default();
+    let weight = TaperedValue {
+        midgame: 500 * sign,
+        endgame: 500 * sign
+    };
+    fn set<T: Clone>(value: T, king: T) -> PieceEvalSet<T> {
+        PieceEvalSet {
+            pawn: value.clone(),
+            knight: value.clone(),
+            bishop: value.clone(),
+            rook: value.clone(),
+            queen: value,
+            king
+        }
+    }
+    evaluator.piece_values = set(2000, 0);
+    evaluator.endgame_piece_values = set(2000, 0);
+    let table = PieceSquareTable([[500 * sign; 8]; 8]);
+    evaluator.midgame_piece_tables = set(table.clone(), table.clone());
+    evaluator.endgame_piece_tables = set(table.clone(), table);
+    evaluator.pawn_structure = PawnStructureWeights {
+        doubled: weight,
+        isolated: weight,
+        backward: weight
+    };
+    evaluator.threats = ThreatWeights {
+        pawn_attacks_piece: weight,
+        minor_attacks_major: weight,
+        hanging: weight
+    };
+    evaluator.mop_up = MopUpWeights {
+        min_advantage: 0,
+        enemy_king_edge: 100 * sign,
+        king_proximity: 100 * sign,
+        bishop_corner: 100 * sign
+    };
+    evaluator.passed_pawns.rook_behind = weight;
+    evaluator.seventh_rank = SeventhRankWeights {
+        rook: weight,
+        queen: weight
+    };
+    evaluator.king_file = KingFileWeights {
+        open: weight,
+        semi_open: weight
+    };
+    evaluator.bad_bishop = BadBishopWeights {
+        same_color_pawn: weight,
+        blocked_pawn: weight
+    };
+    evaluator
+}
+
+#[test]
+fn extreme_weights_do_not_overflow() {
+    let positions = [
+        "k7/8/8/8/8/8/QQQQQQQQ/QRRBBNNK b - - 0 1",
+        "qrrbbnnk/qqqqqqqq/8/8/8/8/8/K7 w - - 0 1",
+        "1k6/8/8/8/8/8/PPPPPPPP/BBBBKBBB w - - 0 1",
+        "4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1"
+    ];
+    for &sign in &[1, -1] {
+        let evaluator = extreme_evaluator(sign);
+        evaluator.validate().unwrap();
+        for fen in positions.iter().chain(POSITIONS) {
+            let board: Board = fen.parse().unwrap();
+            let value = evaluator.evaluate(&board);
+            assert!(matches!(value.kind(), EvalKind::Centipawn(_)), "{} in {}", value, fen);
+            assert_eq!(evaluator.evaluate_verbose(&board).value, value);
+        }
+    }
+}
+
+fn trace_term(fen: &str, name: &str) -> [TaperedValue; 2] {
+    let trace = StandardEvaluator::default().evaluate_verbose(&fen.parse().unwrap());
+    trace.terms.iter().find(|term| term.name == name).unwrap().values
+}
+
+#[test]
+fn threats() {
+    let weights = ThreatWeights::default();
+    //The e5 pawn attacks the defended f6 knight
+    let [white, black] = trace_term("8/4k3/5n2/4P3/8/8/8/4K3 b - - 0 1", "Threats");
+    assert_eq!(white, weights.pawn_attacks_piece);
+    assert_eq!(black, TaperedValue::default());
+
+    //The c3 knight attacks the undefended d5 queen
+    let [white, _] = trace_term("4k3/8/8/3q4/8/2N5/8/4K3 b - - 0 1", "Threats");
+    let expected = TaperedValue {
+        midgame: weights.minor_attacks_major.midgame + weights.hanging.midgame,
+        endgame: weights.minor_attacks_major.endgame + weights.hanging.endgame
+    };
+    assert_eq!(white, expected);
+}
+
+#[test]
+fn halfmove_clock_scaling() {
+    let evaluator = StandardEvaluator::default();
+    let start = evaluator.halfmove_scaling.start as u8;
+    let value = Eval::cp(500);
+    assert_eq!(evaluator.scale_for_halfmove_clock(value, start), value);
+    assert!(evaluator.scale_for_halfmove_clock(value, start + 10) < value);
+    assert_eq!(evaluator.scale_for_halfmove_clock(value, 100), Eval::ZERO);
+    assert_eq!(evaluator.scale_for_halfmove_clock(-value, 100), Eval::ZERO);
+}
+
+#[test]
+fn mop_up_drives_king_to_edge() {
+    let mop_up = |fen: &str| trace_term(fen, "Mop-up")[Color::White.to_index()].endgame;
+    let center = mop_up("8/8/8/3k4/8/8/8/R3K3 w - - 0 1");
+    let edge = mop_up("3k4/8/8/8/8/8/8/R3K3 w - - 0 1");
+    let close = mop_up("3k4/8/3K4/8/8/8/8/R7 w - - 0 1");
+    assert!(center > 0);
+    assert!(edge > center);
+    assert!(close > edge);
+    //Not without a decisive advantage
+    assert_eq!(mop_up("3k4/8/8/8/8/8/8/B3K3 w - - 0 1"), 0);
+}
+
+#[test]
+fn bishop_and_knight_drive_to_bishop_corner() {
+    let mop_up = |fen: &str| trace_term(fen, "Mop-up")[Color::White.to_index()].endgame;
+    //Light squared bishop, so a8 and h1 are the mating corners
+    let right_corner = mop_up("k7/8/2K5/8/8/8/8/3BN3 w - - 0 1");
+    let wrong_corner = mop_up("7k/8/5K2/8/8/8/8/3BN3 w - - 0 1");
+    assert!(right_corner > wrong_corner);
+}
+
+#[test]
+fn rook_behind_passed_pawn() {
+    let weights = PassedPawnWeights::default();
+    let none = TaperedValue::default();
+    //Behind its own passer
+    let [white, black] = trace_term("4k3/8/8/P7/8/8/8/R3K2r w - - 0 1", "Passed pawns");
+    assert_eq!(white, weights.rook_behind);
+    assert_eq!(black, none);
+    //Behind the enemy's passer
+    let [white, _] = trace_term("1R6/7k/8/8/8/1p6/8/4K3 w - - 0 1", "Passed pawns");
+    assert_eq!(white, weights.rook_behind);
+    let [_, black] = trace_term("4k3/8/8/8/8/1P6/8/1r2K3 w - - 0 1", "Passed pawns");
+    assert_eq!(black, weights.rook_behind);
+    //In front of the passer
+    let [white, _] = trace_term("4k3/8/8/8/8/1p6/8/1R2K2r w - - 0 1", "Passed pawns");
+    assert_eq!(white, none);
+    //The pawn isn't passed
+    let [white, _] = trace_term("4k3/1p6/8/P7/8/8/8/R3K3 w - - 0 1", "Passed pawns");
+    assert_eq!(white, none);
+}
+
+#[test]
+fn majors_on_seventh_rank() {
+    let weights = SeventhRankWeights::default();
+    let none = TaperedValue::default();
+    //Attacking pawns on the seventh
+    let [white, black] = trace_term("6k1/pR3ppp/8/8/8/8/5PPP/6K1 w - - 0 1", "Seventh rank");
+    assert_eq!(white, weights.rook);
+    assert_eq!(black, none);
+    //Confining the king to its back rank
+    let [_, black] = trace_term("6k1/8/8/8/8/8/q7/6K1 w - - 0 1", "Seventh rank");
+    assert_eq!(black, weights.queen);
+    //Neither
+    let [_, black] = trace_term("6k1/8/8/8/8/6K1/q7/8 w - - 0 1", "Seventh rank");
+    assert_eq!(black, none);
+}
+
+#[test]
+fn king_on_open_file() {
+    let weights = KingFileWeights::default();
+    let none = TaperedValue::default();
+    let penalty = |value: TaperedValue| TaperedValue {
+        midgame: -value.midgame,
+        endgame: -value.endgame
+    };
+    let [white, black] = trace_term("6k1/5p1p/8/8/8/8/5P1P/6K1 w - - 0 1", "King file");
+    assert_eq!(white, penalty(weights.open));
+    assert_eq!(black, penalty(weights.open));
+    let [white, black] = trace_term("6k1/5ppp/8/8/8/8/5P1P/6K1 w - - 0 1", "King file");
+    assert_eq!(white, penalty(weights.semi_open));
+    assert_eq!(black, none);
+}
+
+#[test]
+fn light_evaluator_skips_heavy_terms() {
+    let board = "8/4k3/5n2/4P3/8/8/5P1P/6K1 b - - 0 1".parse().unwrap();
+    let evaluator = StandardEvaluator {
+        light: true,
+        ..StandardEvaluator::default()
+    };
+    let trace = evaluator.evaluate_verbose(&board);
+    for name in &["Threats", "King file"] {
+        assert!(trace.terms.iter().all(|term| term.name != *name));
+    }
+    assert!(trace.terms.iter().any(|term| term.name == "Passed pawns"));
+}
+
+struct Constant(i16);
+
+impl Evaluator for Constant {
+    fn evaluate(&self, _: &Board) -> Eval {
+        Eval::cp(self.0)
+    }
+}
+
+#[test]
+fn blended_evaluator() {
+    let board = Board::default();
+    let quarter = BlendedEvaluator::<Constant, Constant>::FULL_WEIGHT / 4;
+    let blended = BlendedEvaluator::new(Constant(100), Constant(-100), BlendMode::Blend(quarter));
+    assert_eq!(blended.evaluate(&board), Eval::cp(50));
+
+    let compared = BlendedEvaluator::new(Constant(100), Constant(40), BlendMode::Compare(50));
+    assert_eq!(compared.evaluate(&board), Eval::cp(100));
+    let stats = compared.take_stats();
+    assert_eq!(stats.positions, 1);
+    assert_eq!(stats.divergent_positions, 1);
+    assert_eq!(stats.divergences[0].difference(), -60);
+    assert_eq!(compared.stats().positions, 0);
+}
+
+#[test]
+fn bad_bishop() {
+    let weights = BadBishopWeights::default();
+    //The light squared bishop shares its color with d5 and e4, and d5 is blocked
+    let [white, black] = trace_term("4k3/8/3p4/3P4/4P3/8/8/3BK3 w - - 0 1", "Bad bishop");
+    let same_color = 2;
+    let blocked = 1;
+    assert_eq!(white, TaperedValue {
+        midgame: -(weights.same_color_pawn.midgame * same_color + weights.blocked_pawn.midgame * blocked),
+        endgame: -(weights.same_color_pawn.endgame * same_color + weights.blocked_pawn.endgame * blocked)
+    });
+    assert_eq!(black, TaperedValue::default());
+}
+
+#[test]
+fn mate_scores_relative_to_node() {
+    assert_eq!(Eval::mate_in(5).to_node_relative(2), Eval::mate_in(3));
+    assert_eq!(Eval::mated_in(4).to_node_relative(4), Eval::mated_in(0));
+    assert_eq!(Eval::mate_in(3).to_root_relative(2), Eval::mate_in(5));
+    assert_eq!(Eval::cp(150).to_node_relative(7), Eval::cp(150));
+    assert_eq!(Eval::cp(-150).to_root_relative(7), Eval::cp(-150));
+}
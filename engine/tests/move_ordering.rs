@@ -0,0 +1,213 @@
+use chess::*;
+
+use lunatic::evaluator::*;
+use lunatic::moves::*;
+use lunatic::search::*;
+use lunatic::table::*;
+
+//White to move. exd5 wins the queen for a pawn, Nxd5 wins it for a knight,
+//Rxg5 loses the rook for a knight and Nb5 hangs the knight.
+const CAPTURES: &str = "4k3/8/2p4p/3q2n1/4P3/2N5/8/4K1R1 w - - 0 1";
+
+struct Fixture {
+    board: Board,
+    table: TranspositionTable,
+    killers: KillerTableEntry,
+    history: HistoryTable
+}
+
+impl Fixture {
+    fn new(fen: &str) -> Self {
+        Self {
+            board: fen.parse().unwrap(),
+            table: TranspositionTable::with_rounded_entries(1024),
+            killers: KillerTableEntry::new(),
+            history: [[[0; NUM_SQUARES]; NUM_PIECES]; NUM_COLORS]
+        }
+    }
+
+    fn table_move(mut self, mv: &str) -> Self {
+        self.table.set(&self.board, TableEntry {
+            kind: TableEntryKind::Exact,
+            value: Eval::ZERO,
+            depth: 1,
+            best_move: Some(parse_move(mv))
+        });
+        self
+    }
+
+    fn killer(mut self, mv: &str) -> Self {
+        self.killers.push_back(parse_move(mv));
+        self
+    }
+
+    fn history(mut self, mv: &str, value: u32) -> Self {
+        let mv = parse_move(mv);
+        let piece = self.board.piece_on(mv.get_source()).unwrap();
+        self.history
+            [self.board.side_to_move().to_index()]
+            [piece.to_index()]
+            [mv.get_dest().to_index()] = value;
+        self
+    }
+
+    ///Every move in the order `SortedMoveGenerator` emits it.
+    ///Also checks that each legal move is emitted exactly once.
+    fn ordering(&self) -> Vec<ChessMove> {
+        //Validated like the search does
+        let table_move = self.table
+            .get(&self.board)
+            .and_then(|entry| entry.best_move)
+            .filter(|&mv| move_is_legal(&self.board, mv));
+        let mut moves = SortedMoveGenerator::new(
+            table_move,
+            self.killers.clone(),
+            self.board,
+            MoveGen::new_legal(&self.board)
+        );
+        let mut ordering = Vec::new();
+        while let Some(mv) = moves.next(&self.history, None) {
+            ordering.push(mv);
+        }
+
+        let mut emitted = ordering.clone();
+        let mut legal: Vec<_> = MoveGen::new_legal(&self.board).collect();
+        emitted.sort();
+        legal.sort();
+        assert_eq!(emitted, legal, "every legal move should be emitted exactly once");
+        ordering
+    }
+}
+
+fn parse_move(mv: &str) -> ChessMove {
+    mv.parse().unwrap()
+}
+
+fn moves(moves: &[&str]) -> Vec<ChessMove> {
+    moves.iter().map(|mv| parse_move(mv)).collect()
+}
+
+fn index_of(ordering: &[ChessMove], mv: &str) -> usize {
+    let mv = parse_move(mv);
+    ordering.iter().position(|&m| m == mv).unwrap()
+}
+
+#[test]
+fn table_move_first() {
+    let ordering = Fixture::new(CAPTURES)
+        .table_move("e1e2")
+        .ordering();
+    assert_eq!(ordering[0], parse_move("e1e2"));
+    assert_eq!(ordering[1..3], moves(&["e4d5", "c3d5"])[..]);
+}
+
+#[test]
+fn illegal_table_move_ignored() {
+    let ordering = Fixture::new(CAPTURES)
+        .table_move("e2e4")
+        .ordering();
+    assert_eq!(ordering[..2], moves(&["e4d5", "c3d5"])[..]);
+}
+
+#[test]
+fn winning_captures_by_see_then_losing_captures_last() {
+    let ordering = Fixture::new(CAPTURES).ordering();
+    assert_eq!(ordering[..2], moves(&["e4d5", "c3d5"])[..]);
+    assert_eq!(*ordering.last().unwrap(), parse_move("g1g5"));
+}
+
+#[test]
+fn killers_after_winning_captures() {
+    let ordering = Fixture::new(CAPTURES)
+        .killer("e1f2")
+        .killer("g1g4")
+        .ordering();
+    assert_eq!(ordering[..4], moves(&["e4d5", "c3d5", "e1f2", "g1g4"])[..]);
+}
+
+#[test]
+fn hanging_killers_ordered_with_quiets() {
+    let ordering = Fixture::new(CAPTURES)
+        .killer("c3b5")
+        .killer("e1f2")
+        .ordering();
+    assert_eq!(ordering[..3], moves(&["e4d5", "c3d5", "e1f2"])[..]);
+    assert!(index_of(&ordering, "c3b5") > 2);
+}
+
+#[test]
+fn illegal_killers_skipped() {
+    let ordering = Fixture::new(CAPTURES)
+        .killer("e2e4")
+        .killer("e1f2")
+        .ordering();
+    assert_eq!(ordering[..3], moves(&["e4d5", "c3d5", "e1f2"])[..]);
+}
+
+#[test]
+fn quiets_by_history() {
+    let ordering = Fixture::new(CAPTURES)
+        .history("g1g2", 100)
+        .history("e1f1", 50)
+        .ordering();
+    assert_eq!(ordering[..4], moves(&["e4d5", "c3d5", "g1g2", "e1f1"])[..]);
+}
+
+#[test]
+fn killers_before_history() {
+    let ordering = Fixture::new(CAPTURES)
+        .killer("e1f2")
+        .history("g1g2", 100)
+        .ordering();
+    assert_eq!(ordering[..4], moves(&["e4d5", "c3d5", "e1f2", "g1g2"])[..]);
+}
+
+#[test]
+fn startpos_table_move_then_quiets() {
+    let ordering = Fixture::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+        .table_move("d2d4")
+        .history("e2e4", 10)
+        .ordering();
+    assert_eq!(ordering[..2], moves(&["d2d4", "e2e4"])[..]);
+}
+
+#[test]
+fn move_legality_matches_move_generator() {
+    let positions = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        //Double check
+        "4k3/8/8/8/8/5n2/8/r3K3 w - - 0 1",
+        //En passant would expose the king
+        "8/8/8/KPp4r/8/8/8/7k w - c6 0 2"
+    ];
+    for fen in &positions {
+        let mut board: Board = fen.parse().unwrap();
+        //Also check the positions after the first few legal moves
+        for ply in 0..4 {
+            let legal = MoveGen::new_legal(&board).collect::<Vec<_>>();
+            for source in ALL_SQUARES.iter().copied() {
+                for dest in ALL_SQUARES.iter().copied() {
+                    let promotions = [None, Some(Piece::Queen), Some(Piece::Knight)];
+                    for &promotion in &promotions {
+                        let mv = ChessMove::new(source, dest, promotion);
+                        assert_eq!(move_is_legal(&board, mv), legal.contains(&mv), "{} in {}", mv, board);
+                    }
+                }
+            }
+            match legal.get(ply) {
+                Some(&mv) => board = board.make_move_new(mv),
+                None => break
+            }
+        }
+    }
+}
+
+#[test]
+fn king_captures_after_blocks() {
+    //White is in check from the e8 rook. Bxe8 takes the checker, Re4 and Be2 block,
+    //and Kxd2 takes a knight that isn't checking.
+    let ordering = Fixture::new("4r2k/8/8/1B6/R7/8/3n4/4K3 w - - 0 1").ordering();
+    assert_eq!(ordering[..4], moves(&["b5e8", "b5e2", "a4e4", "e1d2"])[..]);
+}
@@ -0,0 +1,26 @@
+use lunatic::evaluator::*;
+use lunatic::oracle::oracle;
+
+fn oracle_fen(fen: &str) -> Option<Eval> {
+    oracle(&fen.parse().unwrap())
+}
+
+#[test]
+fn two_knights() {
+    //Even with the lone king on the edge
+    assert_eq!(oracle_fen("7k/8/8/8/8/2NN4/8/K7 w - - 0 1"), Some(Eval::DRAW));
+    assert_eq!(oracle_fen("4k3/8/4K3/8/8/8/3NN3/8 b - - 0 1"), Some(Eval::DRAW));
+    //Nf7 is mate
+    assert_eq!(oracle_fen("7k/8/5NK1/6N1/8/8/8/8 w - - 0 1"), None);
+}
+
+#[test]
+fn same_colored_bishops() {
+    //Light squared bishops only
+    assert_eq!(oracle_fen("4k3/8/8/2b5/8/8/1B1B4/4K3 w - - 0 1"), Some(Eval::DRAW));
+    assert_eq!(oracle_fen("4k3/8/8/2b1b3/8/8/1B1B4/4K3 w - - 0 1"), Some(Eval::DRAW));
+    //A dark squared bishop can help mate
+    assert_eq!(oracle_fen("4k3/8/8/2b5/8/8/1BB5/4K3 w - - 0 1"), None);
+    //A pawn can promote
+    assert_eq!(oracle_fen("4k3/8/8/2b5/8/8/1B1B3P/4K3 w - - 0 1"), None);
+}
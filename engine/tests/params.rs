@@ -0,0 +1,31 @@
+use lunatic::search::*;
+
+#[test]
+fn defaults_are_in_range() {
+    let options = SearchOptions::default();
+    for param in TUNABLE_PARAMS {
+        let value = param.get(&options);
+        assert!(param.min <= value && value <= param.max, "{} = {}", param.name, value);
+    }
+}
+
+#[test]
+fn set_by_name() {
+    let mut options = SearchOptions::default();
+    options.set_param("null move eval margin", 123).unwrap();
+    assert_eq!(options.null_move_eval_margin, 123);
+    assert_eq!(options.get_param("Null Move Eval Margin"), Some(123));
+
+    assert!(matches!(options.set_param("Late Move Reduction", 100), Err(ParamError::OutOfRange { .. })));
+    assert!(matches!(options.set_param("Not A Param", 1), Err(ParamError::UnknownParam(_))));
+}
+
+#[test]
+fn switch_by_name() {
+    let mut options = SearchOptions::default();
+    assert_eq!(options.get_switch("null move pruning"), Some(true));
+    options.set_switch("Null Move Pruning", false).unwrap();
+    assert!(!options.null_move_pruning);
+
+    assert!(matches!(options.set_switch("Not A Switch", true), Err(ParamError::UnknownParam(_))));
+}
@@ -0,0 +1,193 @@
+use chess::*;
+
+use lunatic::evaluator::*;
+use lunatic::search::*;
+
+//White to move. Qxd5 wins a queen for free.
+const HANGING_QUEEN: &str = "4k3/8/8/3q4/8/8/3Q4/4K3 w - - 0 1";
+
+//White to move. Ra8 is mate.
+const BACK_RANK_MATE: &str = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+
+#[derive(Default)]
+struct LastResult(Option<SearchResult>);
+
+impl LunaticHandler for LastResult {
+    fn time_up(&mut self) -> bool {
+        false
+    }
+
+    fn search_result(&mut self, search_result: SearchResult) {
+        self.0 = Some(search_result);
+    }
+}
+
+fn best_move(searcher: &mut impl Searcher, fen: &str, limits: SearchLimits) -> ChessMove {
+    let mut handler = LastResult::default();
+    searcher.search(&mut handler, &fen.parse().unwrap(), Vec::new(), limits);
+    handler.0.unwrap().mv
+}
+
+fn alpha_beta_limits() -> SearchLimits {
+    SearchLimits {
+        depth: 4,
+        ..SearchLimits::default()
+    }
+}
+
+fn mcts_limits() -> SearchLimits {
+    SearchLimits {
+        nodes: 2000,
+        ..SearchLimits::default()
+    }
+}
+
+#[test]
+fn alpha_beta_takes_hanging_queen() {
+    let mv = best_move(&mut AlphaBetaSearcher::default(), HANGING_QUEEN, alpha_beta_limits());
+    assert_eq!(mv, "d2d5".parse().unwrap());
+}
+
+#[test]
+fn alpha_beta_finds_mate() {
+    let mv = best_move(&mut AlphaBetaSearcher::default(), BACK_RANK_MATE, alpha_beta_limits());
+    assert_eq!(mv, "a1a8".parse().unwrap());
+}
+
+#[test]
+fn alpha_beta_mate_distance() {
+    //Mate in two, which the transposition table sees at many different plies
+    let fen = "k7/8/2K5/8/8/8/8/7R w - - 0 1";
+    let limits = SearchLimits {
+        depth: 8,
+        ..SearchLimits::default()
+    };
+    let mut handler = LastResult::default();
+    AlphaBetaSearcher::default().search(&mut handler, &fen.parse().unwrap(), Vec::new(), limits);
+    assert_eq!(handler.0.unwrap().value, Eval::mate_in(3));
+}
+
+#[test]
+fn alpha_beta_searches_blended_evaluator() {
+    let primary = StandardEvaluator::default();
+    let mut secondary = primary.clone();
+    secondary.piece_values.queen += 100;
+    let blended = BlendedEvaluator::new(primary, secondary, BlendMode::Compare(0));
+    let mut searcher = AlphaBetaSearcher::default();
+    searcher.options.evaluator = SearchEvaluator::Blended(Box::new(blended.clone()));
+    let mv = best_move(&mut searcher, HANGING_QUEEN, alpha_beta_limits());
+    assert_eq!(mv, "d2d5".parse().unwrap());
+    //The searcher's copy shares the totals
+    let stats = blended.stats();
+    assert!(stats.positions > 0);
+    assert!(stats.divergent_positions > 0);
+}
+
+#[test]
+fn mcts_takes_hanging_queen() {
+    let mv = best_move(&mut MctsSearcher::default(), HANGING_QUEEN, mcts_limits());
+    assert_eq!(mv, "d2d5".parse().unwrap());
+}
+
+#[test]
+fn mcts_finds_mate() {
+    let mv = best_move(&mut MctsSearcher::default(), BACK_RANK_MATE, mcts_limits());
+    assert_eq!(mv, "a1a8".parse().unwrap());
+}
+
+#[test]
+fn no_result_without_legal_moves() {
+    //Black is checkmated
+    let fen = "R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1";
+    let mut handler = LastResult::default();
+    MctsSearcher::default().search(&mut handler, &fen.parse().unwrap(), Vec::new(), mcts_limits());
+    assert!(handler.0.is_none());
+}
+
+#[test]
+fn only_move_stops_immediately() {
+    //White's only legal move is Kxb2
+    let fen = "7k/8/8/8/8/8/1r6/K7 w - - 0 1";
+    let mut handler = LastResult::default();
+    AlphaBetaSearcher::default().search(&mut handler, &fen.parse().unwrap(), Vec::new(), SearchLimits::default());
+    let result = handler.0.unwrap();
+    assert!(result.only_move);
+    assert_eq!(result.depth, 1);
+    assert_eq!(result.mv, "a1b2".parse().unwrap());
+
+    let mut handler = LastResult::default();
+    MctsSearcher::default().search(&mut handler, &fen.parse().unwrap(), Vec::new(), SearchLimits::default());
+    let result = handler.0.unwrap();
+    assert!(result.only_move);
+    assert_eq!(result.nodes, 1);
+}
+
+#[test]
+fn oracle_probes_count_as_tb_hits() {
+    //Kxe2 leaves bare kings, which the oracle resolves
+    let fen = "4k3/8/8/8/8/8/4r3/4K3 w - - 0 1";
+    let mut handler = LastResult::default();
+    AlphaBetaSearcher::default().search(&mut handler, &fen.parse().unwrap(), Vec::new(), alpha_beta_limits());
+    let result = handler.0.unwrap();
+    assert_eq!(result.mv, "e1e2".parse().unwrap());
+    assert!(result.tb_hits > 0);
+}
+
+#[test]
+fn snapshot_keeps_long_game_history() {
+    //Knight shuffles never reset the history, so it grows past 255 positions
+    let moves = ["g1f3", "g8f6", "f3g1", "f6g8"]
+        .iter()
+        .cycle()
+        .take(300)
+        .map(|mv| mv.parse().unwrap())
+        .collect::<Vec<ChessMove>>();
+    let options = SearchOptions {
+        max_depth: 3,
+        ..SearchOptions::default()
+    };
+    let state = |moves: &[ChessMove]| {
+        LunaticSearchState::new(LastResult::default(), &Board::default(), moves.to_vec(), options.clone())
+    };
+    let mut searched = state(&moves);
+    searched.search();
+    let mut bytes = Vec::new();
+    searched.write_snapshot(&mut bytes).unwrap();
+    let mut resumed = state(&moves);
+    assert!(resumed.resume_snapshot(&mut bytes.as_slice()).unwrap());
+    assert!(!resumed.iterations().is_empty());
+    assert_eq!(resumed.iterations(), searched.iterations());
+    //Same position, different history
+    assert!(!state(&moves[..4]).resume_snapshot(&mut bytes.as_slice()).unwrap());
+}
+
+fn search_result(fen: &str, moves: &[&str], depth: u8) -> SearchResult {
+    let mut handler = LastResult::default();
+    let moves = moves.iter().map(|mv| mv.parse().unwrap()).collect();
+    let limits = SearchLimits {
+        depth,
+        ..SearchLimits::default()
+    };
+    AlphaBetaSearcher::default().search(&mut handler, &fen.parse().unwrap(), moves, limits);
+    handler.0.unwrap()
+}
+
+#[test]
+fn perpetual_check_is_a_draw() {
+    //Down two rooks, but Qe8+ Kh7 Qh5+ Kg8 repeats forever
+    let result = search_result("6k1/6p1/8/7Q/8/8/rr6/7K w - - 0 1", &[], 8);
+    assert_eq!(result.mv, "h5e8".parse().unwrap());
+    assert_eq!(result.value, Eval::ZERO);
+}
+
+#[test]
+fn repeating_a_game_position_is_a_draw() {
+    //Down a queen, but Kg1 repeats the position after the first move of the game
+    let moves = ["h1g1", "h8g8", "g1h1", "g8h8"];
+    let result = search_result("q6k/8/8/8/8/8/6PP/7K w - - 0 1", &moves, 5);
+    assert_eq!(result.mv, "h1g1".parse().unwrap());
+    assert_eq!(result.value, Eval::ZERO);
+    //Without the game history, it's just a lost position
+    let result = search_result("q6k/8/8/8/8/8/6PP/7K w - - 0 1", &[], 5);
+    assert!(result.value < Eval::cp(-300));
+}
@@ -0,0 +1,173 @@
+use chess::*;
+
+use lunatic::evaluator::*;
+use lunatic::table::*;
+
+fn entry(depth: u8, kind: TableEntryKind) -> TableEntry {
+    TableEntry {
+        kind,
+        value: Eval::cp(depth as i16),
+        depth,
+        best_move: None
+    }
+}
+
+//Distinct positions, which all share the only bucket of a minimal table.
+fn positions() -> Vec<Board> {
+    let board = Board::default();
+    MoveGen::new_legal(&board).map(|mv| board.make_move_new(mv)).collect()
+}
+
+#[test]
+fn buckets_keep_colliding_entries() {
+    let positions = positions();
+    let mut table = TranspositionTable::with_rounded_entries(1);
+    assert_eq!(table.capacity(), 4);
+    for (depth, board) in positions.iter().take(4).enumerate() {
+        table.set(board, entry(depth as u8 + 1, TableEntryKind::LowerBound));
+    }
+    assert_eq!(table.len(), 4);
+    for board in &positions[..4] {
+        assert!(table.get(board).is_some());
+    }
+
+    //The shallowest entry makes room
+    table.set(&positions[4], entry(3, TableEntryKind::LowerBound));
+    assert!(table.get(&positions[0]).is_none());
+    assert!(table.get(&positions[4]).is_some());
+    assert_eq!(table.len(), 4);
+}
+
+#[test]
+fn replacement_prefers_old_entries() {
+    let positions = positions();
+    let mut table = TranspositionTable::with_rounded_entries(1);
+    table.set(&positions[0], entry(6, TableEntryKind::Exact));
+    table.new_search();
+    for board in &positions[1..4] {
+        table.set(board, entry(4, TableEntryKind::UpperBound));
+    }
+    table.set(&positions[4], entry(1, TableEntryKind::UpperBound));
+    assert!(table.get(&positions[0]).is_none());
+    for board in &positions[1..5] {
+        assert!(table.get(board).is_some());
+    }
+}
+
+#[test]
+fn write_and_read() {
+    let positions = positions();
+    let mut table = TranspositionTable::with_rounded_entries(64);
+    for (depth, board) in positions.iter().enumerate() {
+        table.set(board, entry(depth as u8, TableEntryKind::Exact));
+    }
+    let mut bytes = Vec::new();
+    table.write(&mut bytes).unwrap();
+    let read = TranspositionTable::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(read.capacity(), table.capacity());
+    assert_eq!(read.len(), table.len());
+    for board in &positions {
+        assert_eq!(
+            read.get(board).map(|entry| entry.value),
+            table.get(board).map(|entry| entry.value)
+        );
+    }
+}
+
+#[test]
+fn read_rejects_bad_sizes() {
+    let header = |capacity: u64, len: u64| {
+        let mut bytes = capacity.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes
+    };
+    //Would allocate far more than any Hash setting
+    let bytes = header(1 << 62, 0);
+    assert!(TranspositionTable::read(&mut bytes.as_slice()).is_err());
+    let bytes = header(64, 65);
+    assert!(TranspositionTable::read(&mut bytes.as_slice()).is_err());
+    let bytes = header(64, 0);
+    assert_eq!(TranspositionTable::read(&mut bytes.as_slice()).unwrap().capacity(), 64);
+}
+
+#[test]
+fn save_load_and_resize() {
+    let path = std::env::temp_dir().join(format!("lunatic-tt-{}.bin", std::process::id()));
+    let positions = positions();
+    let mut table = TranspositionTable::with_rounded_entries(64);
+    for board in &positions {
+        table.set(board, entry(5, TableEntryKind::Exact));
+    }
+    table.save(&path).unwrap();
+    let loaded = TranspositionTable::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.len(), table.len());
+
+    let resized = loaded.resized(1024);
+    assert_eq!(resized.capacity(), 1024);
+    for board in &positions {
+        assert!(resized.get(board).is_some());
+    }
+}
+
+#[test]
+fn hashfull_counts_current_search() {
+    let positions = positions();
+    let mut table = TranspositionTable::with_rounded_entries(4);
+    assert_eq!(table.hashfull(), 0);
+    for board in &positions[..2] {
+        table.set(board, entry(1, TableEntryKind::Exact));
+    }
+    assert_eq!(table.hashfull(), 500);
+    table.new_search();
+    assert_eq!(table.hashfull(), 0);
+    assert_eq!(table.len(), 2);
+    table.clear();
+    assert!(table.is_empty());
+    assert!(table.get(&positions[0]).is_none());
+}
+
+#[test]
+fn two_tier_replacement() {
+    let positions = positions();
+    let mut table = TranspositionTable::with_rounded_entries(1);
+    //The deep entry keeps the depth preferred slot through many shallow ones
+    table.set(&positions[0], entry(10, TableEntryKind::Exact));
+    for board in &positions[1..8] {
+        table.set(board, entry(1, TableEntryKind::UpperBound));
+    }
+    assert!(table.get(&positions[0]).is_some());
+    assert!(table.get(&positions[7]).is_some());
+
+    //Once stale, it gives way to recent shallow entries
+    for _ in 0..3 {
+        table.new_search();
+    }
+    for board in &positions[8..12] {
+        table.set(board, entry(1, TableEntryKind::UpperBound));
+    }
+    assert!(table.get(&positions[0]).is_none());
+    for board in &positions[8..12] {
+        assert!(table.get(board).is_some());
+    }
+}
+
+#[test]
+fn stats() {
+    let positions = positions();
+    let mut table = TranspositionTable::with_rounded_entries(1);
+    assert!(table.get(&positions[0]).is_none());
+    for board in &positions[..5] {
+        table.set(board, entry(1, TableEntryKind::Exact));
+    }
+    assert!(table.get(&positions[4]).is_some());
+    assert!(table.get(&positions[5]).is_none());
+    assert_eq!(table.stats(), TtStats {
+        probes: 3,
+        hits: 1,
+        bucket_misses: 1,
+        overwrites: 1
+    });
+    table.new_search();
+    assert_eq!(table.stats(), TtStats::default());
+}
@@ -0,0 +1,155 @@
+use std::time::Duration;
+
+use chess::*;
+
+use lunatic::evaluator::*;
+use lunatic::search::*;
+use lunatic::time::*;
+
+fn result(mv: &str, value: i16) -> SearchResult {
+    result_with_nodes(mv, value, 0)
+}
+
+fn result_with_nodes(mv: &str, value: i16, nodes: u64) -> SearchResult {
+    SearchResult {
+        mv: mv.parse().unwrap(),
+        value: Eval::cp(value),
+        nodes,
+        tb_hits: 0,
+        elapsed: Duration::ZERO,
+        nps: 0,
+        depth: 1,
+        sel_depth: 1,
+        re_searches: 0,
+        principal_variation: Vec::new(),
+        principal_variation_truncated: false,
+        ponder_move: None,
+        transposition_table_size: 0,
+        transposition_table_entries: 0,
+        hashfull: 0,
+        transposition_table_stats: None,
+        easy_move: false,
+        only_move: false,
+        memory_usage: 0,
+        #[cfg(feature = "hash-verification")]
+        transposition_table_collisions: 0
+    }
+}
+
+fn clock(time_left: Duration) -> ClockInfo {
+    ClockInfo {
+        white_time: Some(time_left),
+        ..ClockInfo::untimed(Color::White, 1)
+    }
+}
+
+fn started(mut manager: impl TimeManager, clock: &ClockInfo) -> impl TimeManager {
+    manager.start(clock);
+    manager
+}
+
+fn manager() -> impl TimeManager {
+    started(StandardTimeManager::new(0.1, Duration::ZERO, 0.0, Duration::ZERO), &clock(Duration::from_secs(100)))
+}
+
+#[test]
+fn moves_to_go() {
+    let clock = ClockInfo {
+        moves_to_go: Some(4),
+        ..clock(Duration::from_secs(100))
+    };
+    let mut manager = started(StandardTimeManager::new(0.1, Duration::ZERO, 0.0, Duration::ZERO), &clock);
+    assert_eq!(manager.update(result("e2e4", 0), Duration::ZERO), Duration::from_secs(20));
+}
+
+#[test]
+fn stable_best_move_stops_early() {
+    let mut manager = manager();
+    let mut timeout = Duration::ZERO;
+    for _ in 0..5 {
+        timeout = manager.update(result("e2e4", 0), Duration::from_millis(100));
+    }
+    assert_eq!(timeout, Duration::from_millis(4500));
+}
+
+#[test]
+fn unstable_best_move_thinks_longer() {
+    let mut manager = manager();
+    let mut timeout = Duration::ZERO;
+    for mv in &["e2e4", "d2d4", "e2e4"] {
+        timeout = manager.update(result(mv, 0), Duration::from_millis(100));
+    }
+    assert_eq!(timeout, Duration::from_millis(14700));
+}
+
+#[test]
+fn score_drop_panics() {
+    let manager = StandardTimeManager::new(0.1, Duration::ZERO, 0.25, Duration::ZERO);
+    let mut manager = started(manager, &clock(Duration::from_secs(100)));
+    manager.update(result("e2e4", 20), Duration::from_millis(100));
+    let timeout = manager.update(result("e2e4", -40), Duration::from_millis(100));
+    assert_eq!(timeout, Duration::from_millis(24800));
+    //Recovered, so back to the normal allocation
+    let timeout = manager.update(result("e2e4", -30), Duration::from_millis(100));
+    assert_eq!(timeout, Duration::from_millis(9700));
+}
+
+#[test]
+fn node_count() {
+    let mut manager = NodeCountTimeManager::new(1000);
+    assert_eq!(manager.update(result_with_nodes("e2e4", 0, 999), Duration::from_secs(100)), Duration::MAX);
+    assert_eq!(manager.update(result_with_nodes("e2e4", 0, 1000), Duration::ZERO), Duration::ZERO);
+}
+
+#[test]
+fn emergency_mode() {
+    let manager = StandardTimeManager::new(0.1, Duration::from_secs(1), 0.25, Duration::from_secs(1));
+    let mut manager = started(manager, &clock(Duration::from_secs(100)));
+    assert_eq!(manager.max_depth(), None);
+
+    manager.start(&clock(Duration::from_millis(500)));
+    assert_eq!(manager.max_depth(), Some(8));
+    assert_eq!(manager.update(result("e2e4", 20), Duration::ZERO), Duration::from_millis(10));
+    //Neither a score drop nor an unstable best move extends the allocation
+    assert_eq!(manager.update(result("d2d4", -40), Duration::ZERO), Duration::from_millis(10));
+}
+
+#[test]
+fn skips_iterations_that_wont_finish() {
+    let mut manager = manager();
+    manager.update(result_with_nodes("e2e4", 0, 100), Duration::from_millis(100));
+    let timeout = manager.update(result_with_nodes("e2e4", 0, 300), Duration::from_millis(200));
+    assert_eq!(timeout, Duration::from_millis(9700));
+    //Branching factor 2 predicts 4 seconds, which fits
+    let timeout = manager.update(result_with_nodes("e2e4", 0, 700), Duration::from_secs(2));
+    assert_eq!(timeout, Duration::from_millis(7700));
+    //Branching factor 3 predicts 12 seconds, which doesn't
+    let timeout = manager.update(result_with_nodes("e2e4", 0, 1900), Duration::from_secs(4));
+    assert_eq!(timeout, Duration::ZERO);
+}
+
+#[test]
+fn increment() {
+    let clock = ClockInfo {
+        white_time: Some(Duration::from_secs(50)),
+        black_time: Some(Duration::from_secs(100)),
+        white_increment: Some(Duration::from_secs(4)),
+        black_increment: Some(Duration::from_secs(8)),
+        ..ClockInfo::untimed(Color::Black, 20)
+    };
+    let mut manager = started(StandardTimeManager::new(0.1, Duration::ZERO, 0.0, Duration::ZERO), &clock);
+    assert_eq!(manager.update(result("e7e5", 0), Duration::ZERO), Duration::from_secs(16));
+    //Never more than half the clock
+    manager.start(&ClockInfo {
+        black_time: Some(Duration::from_secs(2)),
+        ..clock
+    });
+    assert_eq!(manager.update(result("e7e5", 0), Duration::ZERO), Duration::from_secs(1));
+}
+
+#[test]
+fn untimed_is_infinite() {
+    let clock = ClockInfo::untimed(Color::White, 1);
+    let mut manager = started(StandardTimeManager::new(0.1, Duration::ZERO, 0.0, Duration::ZERO), &clock);
+    assert_eq!(manager.update(result("e2e4", 0), Duration::from_secs(100)), Duration::MAX - Duration::from_secs(100));
+}
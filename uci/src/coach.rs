@@ -0,0 +1,71 @@
+use std::io::{BufRead, Write, stdin, stdout};
+
+use chess::*;
+
+use lunatic::analysis::*;
+use lunatic::render::{self, RenderStyle};
+use lunatic::search::SearchOptions;
+
+const DEFAULT_DEPTH: u8 = 6;
+const DEFAULT_THRESHOLD: i32 = 200;
+
+fn parse_move(board: &Board, mv: &str) -> Option<ChessMove> {
+    mv.parse()
+        .ok()
+        .or_else(|| ChessMove::from_san(board, mv).ok())
+        .filter(|&mv| board.legal(mv))
+}
+
+///Play against the engine from the starting position.
+///Every move is checked against the engine's best move and blunders are reported immediately.
+///Usage: `coach [depth] [blunder threshold in centipawns] [evaluator file]`
+pub fn coach(mut args: impl Iterator<Item=String>) {
+    let depth = args.next().map_or(DEFAULT_DEPTH, |depth| depth.parse().unwrap());
+    let threshold = args.next().map_or(DEFAULT_THRESHOLD, |threshold| threshold.parse().unwrap());
+    let mut options = SearchOptions::default();
+    if let Some(path) = args.next() {
+        options.evaluator = crate::load_evaluator(&path).into();
+    }
+    let init_pos = Board::default();
+    let mut moves = Vec::new();
+    let mut lines = stdin().lock().lines();
+    loop {
+        let board = moves.iter().fold(init_pos, |board: Board, &mv| board.make_move_new(mv));
+        if board.status() != BoardStatus::Ongoing {
+            println!("Game over: {:?}", board.status());
+            return;
+        }
+        print!("{}", render::board(&board, RenderStyle::Unicode, moves.last().copied()));
+        print!("Your move: ");
+        stdout().flush().unwrap();
+        let line = match lines.next() {
+            Some(line) => line.unwrap(),
+            None => return
+        };
+        let mv = match parse_move(&board, line.trim()) {
+            Some(mv) => mv,
+            None => {
+                println!("Illegal move.");
+                continue;
+            }
+        };
+
+        if let Some(check) = check_move(&init_pos, &moves, mv, &options, depth) {
+            if check.is_blunder(threshold) {
+                println!(
+                    "Blunder! {} scores {} but {} scores {}.",
+                    mv,
+                    check.played_value,
+                    check.best_move,
+                    check.best_value
+                );
+            }
+        }
+        moves.push(mv);
+
+        if let Some(reply) = analyze_position(&init_pos, &moves, &options, depth) {
+            println!("Engine plays {}.", reply.mv);
+            moves.push(reply.mv);
+        }
+    }
+}
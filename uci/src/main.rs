@@ -1,6 +1,7 @@
-use std::io::{BufRead, BufReader, Write, stdin};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write, stdin};
 use std::time::{Instant, Duration};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{channel, Sender};
 use std::sync::atomic::{AtomicBool, Ordering};
 
@@ -8,30 +9,50 @@ use chess::*;
 
 use vampirc_uci::{UciInfoAttribute, UciMessage, UciOptionConfig, UciTimeControl};
 use lunatic::evaluator::*;
+use lunatic::render::{self, RenderStyle};
 use lunatic::search::*;
+use lunatic::table::TranspositionTable;
 use lunatic::time::*;
 use indexmap::IndexMap;
 
 mod bench;
+mod coach;
 
 struct UciHandler {
-    time_manager: StandardTimeManager,
+    time_manager: Box<dyn TimeManager + Send>,
     search_begin: Instant,
     last_update: Instant,
     time_left: Duration,
+    ///Hard cap on the whole search regardless of the time manager
+    max_time: Duration,
+    ///Stop once a mate in at most this many plies is found
+    mate_plies: Option<u8>,
     search_terminator: Arc<AtomicBool>,
+    ///Set while pondering. Cleared by ponderhit.
+    pondering: Arc<AtomicBool>,
+    ///Whether this is a ponder search that hasn't had a ponderhit yet.
+    ///Time isn't managed until then.
+    ponder_search: bool,
     event_sink: Sender<Event>,
     prev_result: Option<SearchResult>
 }
 
 impl LunaticHandler for UciHandler {
     fn time_up(&mut self) -> bool {
+        self.check_ponder_hit();
+        if self.ponder_search {
+            return self.search_terminator.load(Ordering::Acquire);
+        }
         self.time_left < self.last_update.elapsed() ||
+        self.max_time < self.search_begin.elapsed() ||
         self.search_terminator.load(Ordering::Acquire)
     }
 
     fn search_result(&mut self, result: SearchResult) {
-        self.time_left = self.time_manager.update(result.clone(), self.last_update.elapsed());
+        self.check_ponder_hit();
+        if !self.ponder_search {
+            self.manage_time(result.clone(), self.last_update.elapsed());
+        }
         self.last_update = Instant::now();
         self.prev_result = Some(result.clone());
         self.event_sink.send(
@@ -46,7 +67,34 @@ impl LunaticHandler for UciHandler {
 }
 
 impl UciHandler {
+    fn manage_time(&mut self, result: SearchResult, time: Duration) {
+        self.time_left = self.time_manager.update(result.clone(), time);
+        if let (Some(mate_plies), EvalKind::MateIn(plies)) = (self.mate_plies, result.value.kind()) {
+            if plies <= mate_plies {
+                self.time_left = Duration::ZERO;
+            }
+        }
+    }
+
+    ///Turns a ponder search into a normal search after a ponderhit.
+    ///The clock starts now, and the time manager sees the latest result.
+    fn check_ponder_hit(&mut self) {
+        if self.ponder_search && !self.pondering.load(Ordering::Acquire) {
+            self.ponder_search = false;
+            //Time spent pondering doesn't count against the hard cap
+            self.max_time = self.max_time.saturating_add(self.search_begin.elapsed());
+            self.last_update = Instant::now();
+            if let Some(result) = self.prev_result.clone() {
+                self.manage_time(result, Duration::ZERO);
+            }
+        }
+    }
+
     fn finish(mut self) {
+        //The bestmove of a ponder search can't be sent before a ponderhit or stop
+        while self.pondering.load(Ordering::Acquire) && !self.search_terminator.load(Ordering::Acquire) {
+            std::thread::sleep(Duration::from_millis(1));
+        }
         self.event_sink.send(
             Event::EngineSearchUpdate(
                 EngineSearchResult::SearchFinished(
@@ -67,37 +115,199 @@ fn send_message(message: UciMessage) {
     std::io::stdout().flush().unwrap();
 }
 
+fn send_best_move(result: &SearchResult) {
+    send_message(match result.ponder_move {
+        Some(ponder) => UciMessage::best_move_with_ponder(result.mv, ponder),
+        None => UciMessage::best_move(result.mv)
+    });
+}
+
+type OptionHandler = Box<dyn Fn(&mut UciOptions, String)>;
+
 struct UciOptions {
     transposition_table_size: usize,
     search_options: SearchOptions,
     percent_time_used_per_move: f32,
-    minimum_time_used_per_move: Duration
+    ///The hard cap when the score drops sharply
+    panic_percent_time_used_per_move: f32,
+    ///Below this much time left, the time manager switches to emergency mode
+    emergency_time: Duration,
+    minimum_time_used_per_move: Duration,
+    ///Zero means no cap
+    max_time_per_move: Duration,
+    time_odds: f32,
+    node_odds: u64,
+    ///0 picks a new seed for every search
+    handicap_seed: u64,
+    snapshot_file: Option<String>,
+    hash_file: Option<String>,
+    ///Kept between searches. Taken by a search while it runs.
+    transposition_table: Arc<Mutex<Option<TranspositionTable>>>
 }
 
+const MEGABYTE: usize = 1_000_000;
+
+impl Default for UciOptions {
+    fn default() -> Self {
+        Self {
+            transposition_table_size: 4 * MEGABYTE,
+            search_options: SearchOptions {
+                transposition_table_size: 4 * MEGABYTE,
+                ..SearchOptions::default()
+            },
+            percent_time_used_per_move: 0.05f32,
+            panic_percent_time_used_per_move: 0.15f32,
+            emergency_time: Duration::from_millis(1000),
+            minimum_time_used_per_move: Duration::ZERO,
+            max_time_per_move: Duration::ZERO,
+            time_odds: 1.0,
+            node_odds: 0,
+            handicap_seed: 0,
+            snapshot_file: None,
+            hash_file: None,
+            transposition_table: Arc::new(Mutex::new(None))
+        }
+    }
+}
+
+impl UciOptions {
+    ///The engine's effective configuration as YAML, followed by the options only the UCI layer uses.
+    fn dump_config(&self) -> String {
+        let mut config = lunatic::config::dump_config(&self.search_options);
+        config.push_str("uci:
+");
+        config.push_str(&format!("  hash_bytes: {}\n", self.transposition_table_size));
+        config.push_str(&format!("  percent_time_used_per_move: {}\n", self.percent_time_used_per_move * 100.0));
+        config.push_str(&format!("  panic_percent_time_used_per_move: {}\n", self.panic_percent_time_used_per_move * 100.0));
+        config.push_str(&format!("  minimum_time_used_per_move_ms: {}\n", self.minimum_time_used_per_move.as_millis()));
+        config.push_str(&format!("  emergency_time_ms: {}\n", self.emergency_time.as_millis()));
+        config.push_str(&format!("  max_time_per_move_ms: {}\n", self.max_time_per_move.as_millis()));
+        config.push_str(&format!("  time_odds: {}\n", self.time_odds));
+        config.push_str(&format!("  node_odds: {}\n", self.node_odds));
+        config.push_str(&format!("  handicap_seed: {}\n", self.handicap_seed));
+        match &self.snapshot_file {
+            Some(path) => config.push_str(&format!("  snapshot_file: {:?}\n", path)),
+            None => config.push_str("  snapshot_file: null\n")
+        }
+        match &self.hash_file {
+            Some(path) => config.push_str(&format!("  hash_file: {:?}\n", path)),
+            None => config.push_str("  hash_file: null\n")
+        }
+        config
+    }
+}
+
+///Loads an evaluator file for a command line tool, exiting with the error if it can't.
+pub(crate) fn load_evaluator(path: &str) -> StandardEvaluator {
+    StandardEvaluator::from_path(path).unwrap_or_else(|error| {
+        eprintln!("failed to load {}: {}", path, error);
+        std::process::exit(1);
+    })
+}
+
+///How long `quit` waits for an active search to stop
+const QUIT_TIMEOUT: Duration = Duration::from_secs(1);
+
 enum Event {
     UciMessage(UciMessage),
+    ///Sent right before a `go ponder` message.
+    ///vampirc drops the ponder flag when the clock is given as well.
+    GoPonder,
     EngineSearchUpdate(EngineSearchResult)
 }
 
 fn main() {
-    if std::env::args().skip(1).next().as_deref() == Some("bench") {
-        bench::bench();
-        return;
+    match std::env::args().nth(1).as_deref() {
+        Some("bench") => {
+            bench::bench(std::env::args().skip(2));
+            return;
+        }
+        Some("coach") => {
+            coach::coach(std::env::args().skip(2));
+            return;
+        }
+        //Prints the default configuration
+        Some("dumpconfig") => {
+            print!("{}", UciOptions::default().dump_config());
+            return;
+        }
+        //Writes the built in evaluator in the binary format for use with EvalFile
+        Some("export-eval") => {
+            let path = std::env::args().nth(2).expect("Expected an output path");
+            let mut writer = BufWriter::new(File::create(path).unwrap());
+            StandardEvaluator::default().write_binary(&mut writer).unwrap();
+            writer.flush().unwrap();
+            return;
+        }
+        //Explains the evaluation of a position, given as a FEN or the start position by default.
+        //Usage: `eval [--eval-file <path>] [fen]`
+        Some("eval") => {
+            let mut args = std::env::args().skip(2).peekable();
+            let evaluator = match args.next_if(|arg| arg == "--eval-file") {
+                Some(_) => load_evaluator(&args.next().expect("Expected an evaluator file path")),
+                None => StandardEvaluator::default()
+            };
+            let fen = args.collect::<Vec<_>>().join(" ");
+            let board = if fen.is_empty() {
+                Board::default()
+            } else {
+                fen.parse().expect("Expected a valid FEN")
+            };
+            println!("{}", evaluator.evaluate_verbose(&board));
+            return;
+        }
+        //Compares the built in evaluator against an evaluator file on FENs read from stdin,
+        //printing the positions where they disagree by more than the threshold in centipawns.
+        //Usage: `compare-eval <path> [threshold]`
+        Some("compare-eval") => {
+            let candidate = load_evaluator(&std::env::args().nth(2).expect("Expected an evaluator file path"));
+            let threshold = std::env::args()
+                .nth(3)
+                .map_or(50, |threshold| threshold.parse().expect("Expected a threshold in centipawns"));
+            let evaluator = BlendedEvaluator::new(
+                StandardEvaluator::default(),
+                candidate,
+                BlendMode::Compare(threshold)
+            );
+            for line in stdin().lock().lines() {
+                let line = line.unwrap();
+                if let Ok(board) = line.trim().parse::<Board>() {
+                    evaluator.evaluate(&board);
+                }
+            }
+            let stats = evaluator.take_stats();
+            for divergence in &stats.divergences {
+                println!("{} {} {} {:+}", divergence.board, divergence.primary, divergence.secondary, divergence.difference());
+            }
+            let mean_difference = stats.total_difference as f64 / stats.positions.max(1) as f64;
+            println!(
+                "{} positions, {} divergent, mean difference {:.1} centipawns",
+                stats.positions, stats.divergent_positions, mean_difference
+            );
+            return;
+        }
+        //Prints the tunable parameters in OpenBench's SPSA input format
+        Some("spsa") => {
+            let options = SearchOptions::default();
+            for param in TUNABLE_PARAMS {
+                let default = param.get(&options);
+                println!("{}, int, {}, {}, {}, {}, 0.002", param.name, default, param.min, param.max, param.step);
+            }
+            return;
+        }
+        _ => {}
     }
     
-    let mut position: Option<(Board, Vec<ChessMove>)> = None;
+    //The initial position, the moves played from it and its full move number
+    let mut position: Option<(Board, Vec<ChessMove>, u32)> = None;
     let mut search = None;
+    let mut go_ponder = false;
+    let mut debug = false;
 
-    const MEGABYTE: usize = 1000_000;
     //Use IndexMap to preserve options order
-    let mut options_handlers: IndexMap<String, (UciOptionConfig, Box<dyn Fn(&mut UciOptions, String)>)>
+    let mut options_handlers: IndexMap<String, (UciOptionConfig, OptionHandler)>
         = IndexMap::new();
-    let mut options = UciOptions {
-        transposition_table_size: 4 * MEGABYTE,
-        search_options: SearchOptions::default(),
-        percent_time_used_per_move: 0.05f32,
-        minimum_time_used_per_move: Duration::ZERO
-    };
+    let mut options = UciOptions::default();
     macro_rules! add_handlers {
         ($($option:expr => $handler:expr)*) => {
             $({
@@ -113,78 +323,200 @@ fn main() {
         }
     }
     add_handlers! {
+        //The GUI decides when to ponder, so this only advertises support for it
+        UciOptionConfig::Check {
+            name: "Ponder".to_owned(),
+            default: Some(false)
+        } => |_, _| {}
         UciOptionConfig::Spin {
             name: "Hash".to_owned(),
             default: Some((options.transposition_table_size / MEGABYTE) as i64),
             min: Some(0),
-            max: Some(64 * 1000) //64 Gigabytes
+            max: Some((TranspositionTable::MAX_SIZE / MEGABYTE as u64) as i64) //64 Gigabytes
         } => |options, value| {
             options.transposition_table_size = value
                 .parse::<usize>()
                 .unwrap()
-                * MEGABYTE
+                * MEGABYTE;
+            options.search_options.transposition_table_size = options.transposition_table_size;
         }
         UciOptionConfig::Spin {
-            name: "Late Move Reduction".to_owned(),
-            default: Some(options.search_options.late_move_reduction as i64),
+            name: "Percent of time used per move".to_owned(),
+            default: Some((options.percent_time_used_per_move * 100.0) as i64),
             min: Some(0),
-            max: Some(u8::MAX as i64)
+            max: Some(100)
         } => |options, value| {
-            options.search_options.late_move_reduction = value
-                .parse()
-                .unwrap();
+            options.percent_time_used_per_move = value
+                .parse::<f32>()
+                .unwrap()
+                / 100f32;
         }
         UciOptionConfig::Spin {
-            name: "Late Move Leeway".to_owned(),
-            default: Some(options.search_options.late_move_leeway as i64),
+            name: "Percent of time used per move when panicking".to_owned(),
+            default: Some((options.panic_percent_time_used_per_move * 100.0) as i64),
             min: Some(0),
-            max: Some(u8::MAX as i64)
+            max: Some(100)
         } => |options, value| {
-            options.search_options.late_move_leeway = value
+            options.panic_percent_time_used_per_move = value
+                .parse::<f32>()
+                .unwrap()
+                / 100f32;
+        }
+        UciOptionConfig::Spin {
+            name: "Minimum time used per move (ms)".to_owned(),
+            default: Some(options.minimum_time_used_per_move.as_millis() as i64),
+            min: Some(0),
+            max: Some(1000 * 60 * 60 * 24)
+        } => |options, value| {
+            let time = value
                 .parse()
                 .unwrap();
+            options.minimum_time_used_per_move =
+                Duration::from_millis(time);
         }
-        UciOptionConfig::Check {
-            name: "Null Move Pruning".to_owned(),
-            default: Some(options.search_options.null_move_pruning)
+        UciOptionConfig::Spin {
+            name: "Emergency time (ms)".to_owned(),
+            default: Some(options.emergency_time.as_millis() as i64),
+            min: Some(0),
+            max: Some(1000 * 60 * 60 * 24)
         } => |options, value| {
-            options.search_options.null_move_pruning = value
+            let time = value
                 .parse()
                 .unwrap();
+            options.emergency_time =
+                Duration::from_millis(time);
         }
         UciOptionConfig::Spin {
-            name: "Null Move Reduction".to_owned(),
-            default: Some(options.search_options.null_move_reduction as i64),
+            name: "Max Time Per Move (ms)".to_owned(),
+            default: Some(options.max_time_per_move.as_millis() as i64),
             min: Some(0),
-            max: Some(u8::MAX as i64)
+            max: Some(i64::MAX)
         } => |options, value| {
-            options.search_options.null_move_reduction = value
+            let time = value
                 .parse()
                 .unwrap();
+            options.max_time_per_move =
+                Duration::from_millis(time);
         }
         UciOptionConfig::Spin {
-            name: "Percent of time used per move".to_owned(),
-            default: Some((options.percent_time_used_per_move * 100.0) as i64),
-            min: Some(0),
+            name: "Time Odds (%)".to_owned(),
+            default: Some((options.time_odds * 100.0) as i64),
+            min: Some(1),
             max: Some(100)
         } => |options, value| {
-            options.percent_time_used_per_move = value
+            options.time_odds = value
                 .parse::<f32>()
                 .unwrap()
                 / 100f32;
         }
         UciOptionConfig::Spin {
-            name: "Minimum time used per move (ms)".to_owned(),
-            default: Some(options.minimum_time_used_per_move.as_millis() as i64),
+            name: "Node Odds".to_owned(),
+            default: Some(options.node_odds as i64),
             min: Some(0),
-            max: Some(1000 * 60 * 60 * 24)
+            max: Some(i64::MAX)
         } => |options, value| {
-            let time = value
+            options.node_odds = value
                 .parse()
                 .unwrap();
-            options.minimum_time_used_per_move =
-                Duration::from_millis(time);
         }
+        UciOptionConfig::Spin {
+            name: "Handicap Seed".to_owned(),
+            default: Some(options.handicap_seed as i64),
+            min: Some(0),
+            max: Some(i64::MAX)
+        } => |options, value| {
+            options.handicap_seed = value
+                .parse()
+                .unwrap();
+        }
+        UciOptionConfig::String {
+            name: "EvalFile".to_owned(),
+            default: Some("<empty>".to_owned())
+        } => |options, value| {
+            options.search_options.evaluator = match value.as_str() {
+                "" | "<empty>" => SearchEvaluator::default(),
+                path => {
+                    match StandardEvaluator::from_path(path) {
+                        Ok(evaluator) => evaluator.into(),
+                        Err(error) => {
+                            send_message(UciMessage::Info(vec![UciInfoAttribute::String(
+                                format!("failed to load {}: {}", path, error)
+                            )]));
+                            return;
+                        }
+                    }
+                }
+            };
+        }
+        UciOptionConfig::String {
+            name: "Snapshot File".to_owned(),
+            default: Some("<empty>".to_owned())
+        } => |options, value| {
+            //Searches resume from this file, and save to it when ended by stop or quit
+            options.snapshot_file = match value.as_str() {
+                "" | "<empty>" => None,
+                _ => Some(value)
+            };
+        }
+        UciOptionConfig::String {
+            name: "Hash File".to_owned(),
+            default: Some("<empty>".to_owned())
+        } => |options, value| {
+            //The transposition table is loaded from this file if it exists,
+            //and written to it by Save Hash.
+            options.hash_file = match value.as_str() {
+                "" | "<empty>" => None,
+                _ => Some(value)
+            };
+            if let Some(path) = &options.hash_file {
+                match TranspositionTable::load(path) {
+                    Ok(table) => *options.transposition_table.lock().unwrap() = Some(table),
+                    Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(error) => send_message(UciMessage::Info(vec![UciInfoAttribute::String(
+                        format!("failed to load {}: {}", path, error)
+                    )]))
+                }
+            }
+        }
+        UciOptionConfig::Button {
+            name: "Save Hash".to_owned()
+        } => |options, _| {
+            let message = match (&options.hash_file, &*options.transposition_table.lock().unwrap()) {
+                (None, _) => "no hash file set".to_owned(),
+                (Some(_), None) => "no transposition table to save".to_owned(),
+                (Some(path), Some(table)) => match table.save(path) {
+                    Ok(()) => format!("saved {} entries to {}", table.len(), path),
+                    Err(error) => format!("failed to save {}: {}", path, error)
+                }
+            };
+            send_message(UciMessage::Info(vec![UciInfoAttribute::String(message)]));
+        }
+    }
+    //Every other search parameter comes from the registries
+    for param in TUNABLE_PARAMS {
+        let option = UciOptionConfig::Spin {
+            name: param.name.to_owned(),
+            default: Some(param.get(&options.search_options)),
+            min: Some(param.min),
+            max: Some(param.max)
+        };
+        let handler: OptionHandler = Box::new(move |options, value| {
+            let result = param.set(&mut options.search_options, value.parse().unwrap());
+            if let Err(error) = result {
+                send_message(UciMessage::Info(vec![UciInfoAttribute::String(error.to_string())]));
+            }
+        });
+        options_handlers.insert(param.name.to_owned(), (option, handler));
+    }
+    for switch in SWITCH_PARAMS {
+        let option = UciOptionConfig::Check {
+            name: switch.name.to_owned(),
+            default: Some(switch.get(&options.search_options))
+        };
+        let handler: OptionHandler = Box::new(move |options, value| {
+            switch.set(&mut options.search_options, value.parse().unwrap());
+        });
+        options_handlers.insert(switch.name.to_owned(), (option, handler));
     }
 
     let (event_sink, events) = channel();
@@ -193,6 +525,10 @@ fn main() {
         move || {
             let mut lines = BufReader::new(stdin()).lines();
             while let Some(Ok(line)) = lines.next() {
+                let mut tokens = line.split_whitespace();
+                if tokens.next() == Some("go") && tokens.any(|token| token == "ponder") {
+                    let _ = event_sink.send(Event::GoPonder);
+                }
                 let _ = event_sink.send(Event::UciMessage(vampirc_uci::parse_one(&line)));
             }
         }
@@ -200,6 +536,7 @@ fn main() {
 
     'main: while let Ok(event) = events.recv() {
         match event {
+            Event::GoPonder => go_ponder = true,
             Event::UciMessage(message) => match message {
                 UciMessage::Uci => {
                     send_message(UciMessage::id_name("Lunatic"));
@@ -209,125 +546,293 @@ fn main() {
                     }
                     send_message(UciMessage::UciOk);
                 }
-                UciMessage::Debug(_) => {}
+                UciMessage::Debug(on) => debug = on,
                 UciMessage::IsReady => send_message(UciMessage::ReadyOk),
                 UciMessage::SetOption { name, value } => {
                     if let Some((_, handler)) = options_handlers.get(&name) {
-                        handler(&mut options, value.unwrap())
+                        handler(&mut options, value.unwrap_or_default())
+                    }
+                }
+                UciMessage::UciNewGame => {
+                    //Entries loaded from the hash file are deliberately kept until a new game starts.
+                    if let Some(table) = &mut *options.transposition_table.lock().unwrap() {
+                        table.clear();
                     }
                 }
-                UciMessage::UciNewGame => {}
     
                 UciMessage::Position { fen, moves, .. } => {
+                    let move_number = fen
+                        .as_ref()
+                        .and_then(|fen| fen.as_str().split_whitespace().nth(5)?.parse().ok())
+                        .unwrap_or(1);
                     let board = fen
                         .map(|fen| fen.as_str().parse().unwrap())
                         .unwrap_or_default();
-                    position = Some((board, moves));
+                    position = Some((board, moves, move_number));
                 }
                 UciMessage::Go { time_control, search_control } => {
-                    let time_manager;
-                    time_manager = match time_control {
-                        Some(UciTimeControl::MoveTime(time)) => StandardTimeManager::new(
-                            Duration::ZERO,
-                            0.0,
-                            time.to_std().unwrap()
-                        ),
+                    let ponder = std::mem::take(&mut go_ponder);
+                    let (initial_pos, moves, move_number) = position.as_ref().unwrap();
+                    let side_to_move = if moves.len() % 2 == 0 {
+                        initial_pos.side_to_move()
+                    } else {
+                        !initial_pos.side_to_move()
+                    };
+                    let black_moved_first = initial_pos.side_to_move() == Color::Black;
+                    let move_number = move_number + (moves.len() as u32 + black_moved_first as u32) / 2;
+                    let mut clock = ClockInfo::untimed(side_to_move, move_number);
+                    let mut max_time = if options.max_time_per_move.is_zero() {
+                        Duration::MAX
+                    } else {
+                        options.max_time_per_move
+                    };
+                    let mut time_manager: Box<dyn TimeManager + Send> = match time_control {
+                        Some(UciTimeControl::MoveTime(time)) => {
+                            //Also a hard cap, so the search stops on time even mid-iteration
+                            let time = time.to_std().unwrap();
+                            max_time = max_time.min(time);
+                            Box::new(FixedTimeManager::new(time))
+                        }
                         Some(UciTimeControl::TimeLeft {
                             white_time,
                             black_time,
-                            ..
+                            white_increment,
+                            black_increment,
+                            moves_to_go
                         }) => {
-                            let (initial_pos, moves) = position.as_ref().unwrap();
-                            let side_to_move = if moves.len() % 2 == 0 {
-                                initial_pos.side_to_move()
-                            } else {
-                                !initial_pos.side_to_move()
-                            };
-                            let time_left = match side_to_move {
-                                Color::White => white_time,
-                                Color::Black => black_time
-                            }.unwrap().to_std().unwrap();
-                            StandardTimeManager::new(
-                                time_left, 
+                            //Handicap: Pretend we have only a fraction of our clock
+                            let to_clock_time = |time: vampirc_uci::Duration| time.to_std().unwrap().mul_f32(options.time_odds);
+                            clock.white_time = white_time.map(to_clock_time);
+                            clock.black_time = black_time.map(to_clock_time);
+                            clock.white_increment = white_increment.map(|time| time.to_std().unwrap());
+                            clock.black_increment = black_increment.map(|time| time.to_std().unwrap());
+                            clock.moves_to_go = moves_to_go;
+                            Box::new(StandardTimeManager::new(
                                 options.percent_time_used_per_move,
-                                options.minimum_time_used_per_move
-                            )
+                                options.minimum_time_used_per_move,
+                                options.panic_percent_time_used_per_move,
+                                options.emergency_time
+                            ))
                         }
-                        Some(UciTimeControl::Ponder) => todo!(),
-                        None | Some(UciTimeControl::Infinite) => StandardTimeManager::new(
-                            Duration::ZERO,
-                            0.0,
-                            Duration::MAX
-                        )
+                        None | Some(UciTimeControl::Ponder) | Some(UciTimeControl::Infinite) => Box::new(FixedTimeManager::new(Duration::MAX))
                     };
+                    time_manager.start(&clock);
                     
-                    options.search_options.max_depth = 64;
+                    //Limits only apply to this search, so work on a copy of the configured options
+                    let mut search_options = options.search_options.clone();
+                    search_options.handicap_seed = if options.handicap_seed > 0 {
+                        options.handicap_seed
+                    } else {
+                        std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .unwrap()
+                            .as_nanos() as u64
+                    };
+                    //Handicap: Cap the nodes searched per move
+                    if options.node_odds > 0 {
+                        search_options.max_nodes = options.node_odds;
+                    }
+                    let mut mate_plies = None;
                     if let Some(search_control) = search_control {
+                        //The max depth is exclusive
                         if let Some(depth) = search_control.depth {
-                            options.search_options.max_depth = depth;
+                            search_options.max_depth = depth.saturating_add(1);
+                            search_options.endgame_max_depth = depth.saturating_add(1);
                         }
-                        //TODO implement the rest
+                        if let Some(nodes) = search_control.nodes {
+                            search_options.apply_limits(SearchLimits {
+                                nodes,
+                                ..SearchLimits::default()
+                            });
+                        }
+                        if let Some(mate) = search_control.mate {
+                            //A mate in N moves is found by a search of 2N - 1 plies
+                            let plies = (mate.max(1) as u16 * 2 - 1).min(u8::MAX as u16) as u8;
+                            mate_plies = Some(plies);
+                            search_options.apply_limits(SearchLimits {
+                                depth: plies.saturating_add(1),
+                                ..SearchLimits::default()
+                            });
+                        }
+                        if !search_control.search_moves.is_empty() {
+                            search_options.root_moves = Some(search_control.search_moves);
+                        }
+                    }
+                    if let Some(depth) = time_manager.max_depth() {
+                        search_options.apply_limits(SearchLimits {
+                            depth,
+                            ..SearchLimits::default()
+                        });
                     }
-                    let (initial_pos, moves) = position.take().unwrap();
+                    let (initial_pos, moves, _) = position.take().unwrap();
                     let terminator = Arc::new(AtomicBool::new(false));
+                    let pondering = Arc::new(AtomicBool::new(ponder));
                     let mut handler = UciHandler {
                         time_manager,
                         search_begin: Instant::now(),
                         last_update: Instant::now(),
                         time_left: Duration::MAX,
+                        max_time,
+                        mate_plies,
                         search_terminator: Arc::clone(&terminator),
+                        pondering: Arc::clone(&pondering),
+                        ponder_search: ponder,
                         event_sink: event_sink.clone(),
                         prev_result: None,
                     };
-                    std::thread::spawn({
-                        let options = options.search_options.clone();
+                    let search_thread = std::thread::spawn({
+                        let snapshot_file = options.snapshot_file.clone();
+                        let stopped = Arc::clone(&terminator);
+                        let shared_table = Arc::clone(&options.transposition_table);
+                        let entries = TranspositionTable::rounded_entries(options.transposition_table_size);
+                        let options = search_options;
                         move || {
-                            let mut search_state = LunaticSearchState::new(
+                            let table = match shared_table.lock().unwrap().take() {
+                                Some(table) if table.capacity() == entries => table,
+                                Some(table) => table.resized(entries),
+                                None => TranspositionTable::with_rounded_entries(entries)
+                            };
+                            let mut search_state = LunaticSearchState::with_table(
                                 &mut handler,
                                 &initial_pos,
                                 moves,
-                                options
+                                options,
+                                table
                             );
+                            if let Some(snapshot_file) = &snapshot_file {
+                                //A missing file just means there's nothing to resume yet
+                                if let Ok(file) = File::open(snapshot_file) {
+                                    let resumed = search_state.resume_snapshot(&mut BufReader::new(file));
+                                    if let Err(error) = resumed {
+                                        send_message(UciMessage::Info(vec![UciInfoAttribute::String(
+                                            format!("failed to read snapshot: {}", error)
+                                        )]));
+                                    }
+                                }
+                            }
                             search_state.search();
+                            //Only the user stopping a search asks for it to be resumed later
+                            if let Some(snapshot_file) = snapshot_file.as_ref().filter(|_| stopped.load(Ordering::Acquire)) {
+                                let written = File::create(snapshot_file).and_then(|file| {
+                                    let mut writer = BufWriter::new(file);
+                                    search_state.write_snapshot(&mut writer)?;
+                                    writer.flush()
+                                });
+                                if let Err(error) = written {
+                                    send_message(UciMessage::Info(vec![UciInfoAttribute::String(
+                                        format!("failed to write snapshot: {}", error)
+                                    )]));
+                                }
+                            }
+                            *shared_table.lock().unwrap() = Some(search_state.into_table());
                             handler.finish();
                         }
                     });
-                    search = Some(terminator);
+                    search = Some((terminator, pondering, search_thread));
                 }
-                UciMessage::Stop => if let Some(search) = &mut search {
-                    search.store(true, Ordering::Release);
+                UciMessage::Stop => if let Some((terminator, _, _)) = &search {
+                    terminator.store(true, Ordering::Release);
                 },
                 
-                UciMessage::PonderHit => {}
-                UciMessage::Quit => break 'main,
+                UciMessage::PonderHit => if let Some((_, pondering, _)) = &search {
+                    pondering.store(false, Ordering::Release);
+                },
+                UciMessage::Quit => {
+                    if let Some((terminator, _, search_thread)) = search.take() {
+                        //Stop and wait for the search so its bestmove
+                        //is reported before exiting instead of during teardown.
+                        terminator.store(true, Ordering::Release);
+                        if options.snapshot_file.is_some() {
+                            //The search saves its snapshot as it ends, however long that takes
+                            search_thread.join().unwrap();
+                        } else {
+                            let deadline = Instant::now() + QUIT_TIMEOUT;
+                            while !search_thread.is_finished() && Instant::now() < deadline {
+                                std::thread::sleep(Duration::from_millis(1));
+                            }
+                        }
+                        for event in events.try_iter() {
+                            if let Event::EngineSearchUpdate(EngineSearchResult::SearchFinished(result)) = event {
+                                send_best_move(&result);
+                            }
+                        }
+                    }
+                    break 'main;
+                }
                 UciMessage::Register { .. } => {}
+                UciMessage::Unknown(command, _) if command.trim() == "d" => {
+                    if let Some((initial_pos, moves, _)) = &position {
+                        let board = moves
+                            .iter()
+                            .fold(*initial_pos, |board, &mv| board.make_move_new(mv));
+                        print!("{}", render::board(&board, RenderStyle::Ascii, moves.last().copied()));
+                        println!("Fen: {}", render::fen(&board));
+                        std::io::stdout().flush().unwrap();
+                    }
+                }
+                UciMessage::Unknown(command, _) if command.trim() == "eval" => {
+                    if let Some((initial_pos, moves, _)) = &position {
+                        let board = moves
+                            .iter()
+                            .fold(*initial_pos, |board, &mv| board.make_move_new(mv));
+                        println!("{}", options.search_options.evaluator.standard().evaluate_verbose(&board));
+                        std::io::stdout().flush().unwrap();
+                    }
+                }
+                UciMessage::Unknown(command, _) if command.trim() == "dumpconfig" => {
+                    print!("{}", options.dump_config());
+                    std::io::stdout().flush().unwrap();
+                }
                 UciMessage::Unknown(_, _) => {}
                 //Engine to GUI messages
                 _ => {}
             }
             Event::EngineSearchUpdate(result) => match result {
                 EngineSearchResult::SearchInfo(result, duration) => {
-                    let tt_filledness =
-                        result.transposition_table_entries
-                        * 1000
-                        / result.transposition_table_size;
                     send_message(UciMessage::Info(vec![
                         match result.value.kind() {
                             EvalKind::Centipawn(cp) => UciInfoAttribute::from_centipawns(cp as i32),
-                            EvalKind::MateIn(m) => UciInfoAttribute::from_mate(((m + 1) / 2) as i8),
-                            EvalKind::MatedIn(m) => UciInfoAttribute::from_mate(-(((m + 1) / 2) as i8))
+                            EvalKind::MateIn(m) => UciInfoAttribute::from_mate(m.div_ceil(2) as i8),
+                            EvalKind::MatedIn(m) => UciInfoAttribute::from_mate(-(m.div_ceil(2) as i8))
                         },
                         UciInfoAttribute::Depth(result.depth),
                         UciInfoAttribute::SelDepth(result.sel_depth),
-                        UciInfoAttribute::Nodes(result.nodes as u64),
+                        UciInfoAttribute::Nodes(result.nodes),
+                        UciInfoAttribute::Nps(result.nps),
+                        UciInfoAttribute::TbHits(result.tb_hits),
                         UciInfoAttribute::Pv(result.principal_variation.clone()),
                         UciInfoAttribute::Time(vampirc_uci::Duration::from_std(duration).unwrap()),
-                        UciInfoAttribute::HashFull(tt_filledness as u16)
+                        UciInfoAttribute::HashFull(result.hashfull as u16)
+                    ]));
+                    if debug {
+                        send_message(UciMessage::Info(vec![
+                            UciInfoAttribute::String(format!(
+                                "memory {} bytes",
+                                result.memory_usage
+                            ))
+                        ]));
+                        if let Some(stats) = result.transposition_table_stats {
+                            send_message(UciMessage::Info(vec![
+                                UciInfoAttribute::String(format!(
+                                    "hash probes {} hits {} bucket misses {} overwrites {}",
+                                    stats.probes, stats.hits, stats.bucket_misses, stats.overwrites
+                                ))
+                            ]));
+                        }
+                    }
+                    #[cfg(feature = "hash-verification")]
+                    send_message(UciMessage::Info(vec![
+                        UciInfoAttribute::String(format!(
+                            "hash collisions {}",
+                            result.transposition_table_collisions
+                        ))
                     ]));
                 }
                 EngineSearchResult::SearchFinished(result) => {
-                    send_message(UciMessage::best_move(result.mv));
-                    search = None;
+                    send_best_move(&result);
+                    if let Some((_, _, search_thread)) = search.take() {
+                        search_thread.join().unwrap();
+                    }
                 }
             }
         }
@@ -0,0 +1,86 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+///Runs the commands followed by `quit` and returns the output once the engine exits.
+///An empty command waits a bit before sending the rest.
+fn run(commands: &[&str]) -> Vec<String> {
+    let mut engine = Command::new(env!("CARGO_BIN_EXE_lunatic-uci"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = engine.stdin.take().unwrap();
+    let stdout = BufReader::new(engine.stdout.take().unwrap());
+    for command in commands {
+        if command.is_empty() {
+            std::thread::sleep(Duration::from_millis(300));
+        } else {
+            writeln!(stdin, "{}", command).unwrap();
+        }
+    }
+    //Quit only once the search is done
+    let mut output = Vec::new();
+    for line in stdout.lines() {
+        let line = line.unwrap();
+        let done = line.starts_with("bestmove ");
+        output.push(line);
+        if done {
+            break;
+        }
+    }
+    writeln!(stdin, "quit").unwrap();
+    assert!(engine.wait().unwrap().success());
+    output
+}
+
+fn best_move(output: &[String]) -> &str {
+    output.last().unwrap().split_whitespace().nth(1).unwrap()
+}
+
+#[test]
+fn go_depth() {
+    let output = run(&["position startpos", "go depth 1"]);
+    assert_eq!(best_move(&output), "e2e4");
+    assert!(output.iter().all(|line| !line.contains("depth 2")));
+}
+
+#[test]
+fn go_mate() {
+    let output = run(&["position fen k7/8/2K5/8/8/8/8/7R w - - 0 1", "go mate 2"]);
+    assert_eq!(best_move(&output), "c6c7");
+    assert!(output.iter().any(|line| line.contains("score mate 2")));
+}
+
+#[test]
+fn go_nodes() {
+    let output = run(&["position startpos", "go nodes 1000"]);
+    assert!(output.iter().all(|line| !line.contains("depth 10")));
+}
+
+#[test]
+fn go_searchmoves() {
+    let output = run(&["position startpos", "go depth 4 searchmoves a2a3 h2h3"]);
+    assert!(["a2a3", "h2h3"].contains(&best_move(&output)));
+}
+
+#[test]
+fn go_ponder() {
+    //Mate in one is found right away, but the bestmove has to wait for the ponderhit
+    let output = run(&[
+        "position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+        "go ponder wtime 10000 btime 10000",
+        "",
+        "isready",
+        "ponderhit"
+    ]);
+    let ready = output.iter().position(|line| line == "readyok").unwrap();
+    assert!(output[..ready].iter().all(|line| !line.starts_with("bestmove ")));
+    assert_eq!(best_move(&output), "a1a8");
+}
+
+#[test]
+fn go_ponder_stop() {
+    let output = run(&["position startpos moves e2e4", "go ponder", "", "stop"]);
+    assert!(output.last().unwrap().starts_with("bestmove "));
+}
@@ -0,0 +1,72 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+use lunatic::config::dump_config;
+use lunatic::search::*;
+
+fn uci_options() -> Vec<String> {
+    let mut engine = Command::new(env!("CARGO_BIN_EXE_lunatic-uci"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = engine.stdin.take().unwrap();
+    writeln!(stdin, "uci").unwrap();
+    writeln!(stdin, "quit").unwrap();
+    let options = BufReader::new(engine.stdout.take().unwrap())
+        .lines()
+        .map(Result::unwrap)
+        .filter(|line| line.starts_with("option "))
+        .collect::<Vec<_>>();
+    assert!(engine.wait().unwrap().success());
+    options
+}
+
+#[test]
+fn search_params_are_options() {
+    let options = uci_options();
+    let defaults = SearchOptions::default();
+    for param in TUNABLE_PARAMS {
+        let option = format!(
+            "option name {} type spin default {} min {} max {}",
+            param.name, param.get(&defaults), param.min, param.max
+        );
+        assert!(options.contains(&option), "missing {:?}", option);
+    }
+    for switch in SWITCH_PARAMS {
+        let option = format!("option name {} type check default {}", switch.name, switch.get(&defaults));
+        assert!(options.contains(&option), "missing {:?}", option);
+    }
+}
+
+#[test]
+fn numeric_search_options_are_options() {
+    //Option names in snake case, to match them to field names
+    let names = uci_options()
+        .iter()
+        .map(|option| {
+            let name = &option["option name ".len()..option.find(" type ").unwrap()];
+            name.to_ascii_lowercase().replace(' ', "_")
+        })
+        .collect::<Vec<_>>();
+    //The config dump lists every field as `  name: value` under `search:`
+    let config = dump_config(&SearchOptions::default());
+    let fields = config
+        .lines()
+        .skip_while(|&line| line != "search:")
+        .skip(1)
+        .take_while(|line| line.starts_with("  "))
+        .filter_map(|line| line.trim().split_once(": "))
+        .filter(|(_, value)| value.parse::<i128>().is_ok())
+        .map(|(field, _)| field)
+        .collect::<Vec<_>>();
+    assert!(fields.contains(&"late_move_reduction"));
+    for field in fields {
+        let name = match field {
+            //Set through node odds and `go nodes`
+            "max_nodes" => "node_odds",
+            field => field
+        };
+        assert!(names.iter().any(|option| option == name), "{} is not an option", field);
+    }
+}
@@ -0,0 +1,35 @@
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+#[test]
+fn quit_during_search() {
+    let mut engine = Command::new(env!("CARGO_BIN_EXE_lunatic-uci"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = engine.stdin.take().unwrap();
+    writeln!(stdin, "position startpos").unwrap();
+    writeln!(stdin, "go infinite").unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+    writeln!(stdin, "quit").unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let status = loop {
+        if let Some(status) = engine.try_wait().unwrap() {
+            break status;
+        }
+        if Instant::now() > deadline {
+            engine.kill().unwrap();
+            panic!("engine did not exit after quit");
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    };
+    assert!(status.success());
+
+    let mut output = String::new();
+    engine.stdout.take().unwrap().read_to_string(&mut output).unwrap();
+    let last_line = output.lines().last().unwrap();
+    assert!(last_line.starts_with("bestmove "), "unexpected last line {:?}", last_line);
+}